@@ -0,0 +1,6 @@
+pub mod dap;
+pub mod debug;
+pub mod lex;
+pub mod repl;
+pub mod test;
+pub mod trace;
@@ -0,0 +1,697 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+};
+
+use gnarly_interpreter::{
+    execution_context::output::Output,
+    interpreter::{
+        ExecutionStatus, Interpreter, InterpreterConfig, Operand,
+        error::{ErrorKind, RuntimeError},
+    },
+    lexer::{Lexer, Position, SourceToken},
+};
+use serde_json::{Value, json};
+
+// @NOTE Debug Adapter Protocol server for debugging from an editor, see
+// https://microsoft.github.io/debug-adapter-protocol/specification
+// The protocol uses stdin and stdout, so the program cannot read stdin and what it prints
+// is sent as `output` events. Breakpoints stop at the first token on (or after) their line,
+// and steps are one token long, like in `gnarly debug`.
+//
+// Variables references are:
+//  - `OPERAND_STACK_REFERENCE` for the operand stack of the current scope
+//  - `SCOPE_REFERENCE_BASE + depth` for the variables of each scope
+//  - `VALUE_REFERENCE_BASE + index` for arrays and objects shown since the program stopped
+
+const THREAD_ID: i64 = 1;
+const OPERAND_STACK_REFERENCE: usize = 1;
+const SCOPE_REFERENCE_BASE: usize = 2;
+const VALUE_REFERENCE_BASE: usize = 1_000_000;
+
+/// Sends numbered messages to the client.
+struct Connection {
+    writer: Box<dyn Write + Send>,
+    seq: i64,
+}
+
+type SharedConnection = Arc<Mutex<Connection>>;
+
+fn send(connection: &SharedConnection, mut message: Value) {
+    let mut connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+    connection.seq += 1;
+    message["seq"] = json!(connection.seq);
+    let body = message.to_string();
+    // If the client has gone away, the server stops once it notices stdin is closed
+    let _ = write!(
+        connection.writer,
+        "Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = connection.writer.flush();
+}
+
+fn event(event: &str, body: Value) -> Value {
+    json!({ "type": "event", "event": event, "body": body })
+}
+
+/// Sends what the program prints to the client as `output` events.
+struct OutputEvents {
+    connection: SharedConnection,
+    category: &'static str,
+}
+
+impl Write for OutputEvents {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        send(
+            &self.connection,
+            event(
+                "output",
+                json!({ "category": self.category, "output": String::from_utf8_lossy(buf) }),
+            ),
+        );
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Read one message, or `None` once the client has closed the stream.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+#[derive(Clone, Copy)]
+enum RunMode {
+    Continue,
+    StepIn,
+    /// Stop once back at this call depth or shallower
+    StepOver(usize),
+    /// Stop once shallower than this call depth
+    StepOut(usize),
+}
+
+enum State {
+    /// Waiting for `launch` and `configurationDone`
+    NotStarted,
+    Stopped,
+    Running(RunMode),
+    Finished,
+}
+
+struct Breakpoint {
+    id: i64,
+    position: Position,
+    condition: Option<Vec<SourceToken>>,
+}
+
+struct Server {
+    connection: SharedConnection,
+    symbols: Vec<String>,
+    interpreter: Interpreter,
+    state: State,
+    failed: bool,
+    /// Tokens of the program, once launched
+    program: Option<Vec<SourceToken>>,
+    stop_on_entry: bool,
+    /// Breakpoints by the canonical path of their file
+    breakpoints: HashMap<PathBuf, Vec<Breakpoint>>,
+    next_breakpoint_id: i64,
+    canonical_paths: HashMap<Arc<Path>, PathBuf>,
+    /// Arrays and objects that can be expanded in the variables view
+    values: Vec<Operand>,
+    /// Events to send once the response to the current request has been sent
+    events: Vec<Value>,
+}
+
+/// Serve a single debugging session over stdio, returning whether the program ran without
+/// an error.
+pub fn run(symbols: &[String], mut config: InterpreterConfig) -> Result<bool, String> {
+    // stdin carries the protocol
+    config.capabilities.stdin = false;
+
+    let connection = Arc::new(Mutex::new(Connection {
+        writer: Box::new(io::stdout()),
+        seq: 0,
+    }));
+    let mut interpreter = Interpreter::new(config);
+    interpreter.context.output = Output::new(
+        OutputEvents {
+            connection: connection.clone(),
+            category: "stdout",
+        },
+        OutputEvents {
+            connection: connection.clone(),
+            category: "stderr",
+        },
+    );
+
+    // Requests are read on another thread so that `pause` arrives while the program runs
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(io::stdin());
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(message)) => {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    eprintln!("Error reading debug adapter message: {}", err);
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut server = Server {
+        connection,
+        symbols: symbols.to_vec(),
+        interpreter,
+        state: State::NotStarted,
+        failed: false,
+        program: None,
+        stop_on_entry: false,
+        breakpoints: HashMap::new(),
+        next_breakpoint_id: 1,
+        canonical_paths: HashMap::new(),
+        values: Vec::new(),
+        events: Vec::new(),
+    };
+    server.serve(messages);
+    Ok(!server.failed)
+}
+
+impl Server {
+    fn serve(&mut self, messages: Receiver<Value>) {
+        loop {
+            let message = match self.state {
+                State::Running(mode) => match messages.try_recv() {
+                    Ok(message) => message,
+                    Err(TryRecvError::Empty) => {
+                        self.execute_step(mode);
+                        self.send_events();
+                        continue;
+                    }
+                    Err(TryRecvError::Disconnected) => return,
+                },
+                _ => match messages.recv() {
+                    Ok(message) => message,
+                    Err(_) => return,
+                },
+            };
+
+            let keep_serving = self.handle_message(&message);
+            self.send_events();
+            if !keep_serving {
+                return;
+            }
+        }
+    }
+
+    /// Handle a request, returning false once the client has disconnected.
+    fn handle_message(&mut self, message: &Value) -> bool {
+        if message["type"] != "request" {
+            return true;
+        }
+        let command = message["command"].as_str().unwrap_or_default();
+        let arguments = &message["arguments"];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(self.scopes()),
+            "variables" => self.variables(arguments),
+            "evaluate" => self.evaluate(arguments),
+            "continue" => self
+                .resume(RunMode::Continue)
+                .map(|_| json!({ "allThreadsContinued": true })),
+            "next" => self.resume(RunMode::StepOver(self.interpreter.call_depth())),
+            "stepIn" => self.resume(RunMode::StepIn),
+            "stepOut" => self.resume(RunMode::StepOut(self.interpreter.call_depth())),
+            "pause" => {
+                if matches!(self.state, State::Running(_)) {
+                    self.stop("pause", None);
+                }
+                Ok(json!({}))
+            }
+            "disconnect" | "terminate" => {
+                self.respond(message, Ok(json!({})));
+                return false;
+            }
+            _ => Err(format!("Unsupported request: {}", command)),
+        };
+        self.respond(message, result);
+        true
+    }
+
+    fn respond(&self, request: &Value, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        send(&self.connection, response);
+    }
+
+    fn queue_event(&mut self, name: &str, body: Value) {
+        self.events.push(event(name, body));
+    }
+
+    fn send_events(&mut self) {
+        for event in self.events.drain(..) {
+            send(&self.connection, event);
+        }
+    }
+
+    /// `{ program, stopOnEntry }`
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or("launch: Missing 'program'")?;
+        self.program = Some(crate::read_program(
+            Path::new(program),
+            &self.symbols,
+            &self.interpreter.context.capabilities,
+        )?);
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+
+        // Breakpoints can be mapped to tokens now
+        self.queue_event("initialized", json!({}));
+        Ok(json!({}))
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        let Some(program) = self.program.clone() else {
+            return Ok(json!({}));
+        };
+
+        // Stop before the first token
+        match self.interpreter.run_paused(program) {
+            Ok(ExecutionStatus::Paused) if self.stop_on_entry => self.stop("entry", None),
+            Ok(ExecutionStatus::Paused) => match self.breakpoint_hit() {
+                Some(id) => self.stop("breakpoint", Some(id)),
+                None => self.state = State::Running(RunMode::Continue),
+            },
+            Ok(ExecutionStatus::Completed) => self.finish(None),
+            Err(err) => self.finish(Some(err)),
+        }
+        Ok(json!({}))
+    }
+
+    fn resume(&mut self, mode: RunMode) -> Result<Value, String> {
+        if !matches!(self.state, State::Stopped) {
+            return Err("The program is not stopped".to_string());
+        }
+        self.values.clear();
+        self.state = State::Running(mode);
+        Ok(json!({}))
+    }
+
+    fn execute_step(&mut self, mode: RunMode) {
+        match self.interpreter.step() {
+            Ok(ExecutionStatus::Paused) => {
+                if let Some(id) = self.breakpoint_hit() {
+                    self.stop("breakpoint", Some(id));
+                    return;
+                }
+                let call_depth = self.interpreter.call_depth();
+                let done = match mode {
+                    RunMode::Continue => false,
+                    RunMode::StepIn => true,
+                    RunMode::StepOver(depth) => call_depth <= depth,
+                    RunMode::StepOut(depth) => call_depth < depth,
+                };
+                if done {
+                    self.stop("step", None);
+                }
+            }
+            Ok(ExecutionStatus::Completed) => self.finish(None),
+            Err(err) => self.finish(Some(err)),
+        }
+    }
+
+    fn stop(&mut self, reason: &str, breakpoint_id: Option<i64>) {
+        self.state = State::Stopped;
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(id) = breakpoint_id {
+            body["hitBreakpointIds"] = json!([id]);
+        }
+        self.queue_event("stopped", body);
+    }
+
+    fn finish(&mut self, error: Option<RuntimeError>) {
+        self.state = State::Finished;
+        // `exit` is not an error, so only its code is reported
+        if let Some(err) = &error
+            && !matches!(err.kind, ErrorKind::Exit(_))
+        {
+            let mut output = format!("Error: {}\n", err);
+            for frame in &err.trace {
+                output.push_str(&format!("    at {}\n", frame));
+            }
+            self.queue_event("output", json!({ "category": "stderr", "output": output }));
+        }
+        let exit_code = error.as_ref().map_or(0, crate::error_exit_code);
+        self.failed = exit_code != 0;
+        self.queue_event("exited", json!({ "exitCode": exit_code }));
+        self.queue_event("terminated", json!({}));
+    }
+
+    fn canonical_path(&mut self, file: &Arc<Path>) -> PathBuf {
+        self.canonical_paths
+            .entry(file.clone())
+            .or_insert_with(|| canonicalize(file))
+            .clone()
+    }
+
+    /// Breakpoint at the token the program is about to execute, if its condition holds.
+    fn breakpoint_hit(&mut self) -> Option<i64> {
+        let source_token = self.interpreter.next_token()?;
+        let position = source_token.position;
+        let file = source_token.file.clone()?;
+        let path = self.canonical_path(&file);
+
+        let breakpoint = self
+            .breakpoints
+            .get(&path)?
+            .iter()
+            .find(|breakpoint| breakpoint.position == position)?;
+        let id = breakpoint.id;
+        let Some(condition) = breakpoint.condition.clone() else {
+            return Some(id);
+        };
+
+        match self.interpreter.evaluate(condition) {
+            Ok(operands) => {
+                let operand = operands.last()?;
+                self.interpreter
+                    .context
+                    .operand_is_truthy(operand)
+                    .unwrap_or(false)
+                    .then_some(id)
+            }
+            // Stop so that the condition can be fixed
+            Err(err) => {
+                self.queue_event(
+                    "output",
+                    json!({
+                        "category": "console",
+                        "output": format!("Error in breakpoint condition: {}\n", err),
+                    }),
+                );
+                Some(id)
+            }
+        }
+    }
+
+    /// `{ source: { path }, breakpoints: [{ line, column?, condition? }] }`
+    /// Each breakpoint is moved to the first token on its line (at or after its column), or
+    /// the first token after it.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["source"]["path"]
+            .as_str()
+            .ok_or("setBreakpoints: Missing source path")?;
+        let canonical = canonicalize(Path::new(path));
+        let positions = self.token_positions(&canonical)?;
+
+        let mut breakpoints = Vec::new();
+        let mut results = Vec::new();
+        for requested in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(0) as usize;
+            let column = requested["column"].as_u64().map(|column| column as usize);
+            let position = positions.iter().find(|position| {
+                (position.line == line && column.is_none_or(|column| position.column >= column))
+                    || position.line > line
+            });
+            let Some(&position) = position else {
+                results
+                    .push(json!({ "verified": false, "message": "No code at or after this line" }));
+                continue;
+            };
+            let condition = match requested["condition"].as_str() {
+                Some(condition) if !condition.trim().is_empty() => match Lexer::scan(condition) {
+                    Ok(lexer) => Some(lexer.token_list),
+                    Err(err) => {
+                        results.push(json!({
+                            "verified": false,
+                            "message": format!("Lexer error in condition: {}", err),
+                        }));
+                        continue;
+                    }
+                },
+                _ => None,
+            };
+
+            let id = self.next_breakpoint_id;
+            self.next_breakpoint_id += 1;
+            breakpoints.push(Breakpoint {
+                id,
+                position,
+                condition,
+            });
+            results.push(json!({
+                "id": id,
+                "verified": true,
+                "line": position.line,
+                "column": position.column,
+                "source": { "path": path },
+            }));
+        }
+
+        self.breakpoints.insert(canonical, breakpoints);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    /// Positions of the tokens in a file, in order. Files that are not part of the program
+    /// itself (e.g. imported modules) are lexed separately.
+    fn token_positions(&mut self, path: &Path) -> Result<Vec<Position>, String> {
+        let mut positions = Vec::new();
+        for source_token in self.program.clone().unwrap_or_default() {
+            if let Some(file) = &source_token.file
+                && self.canonical_path(file) == path
+            {
+                positions.push(source_token.position);
+            }
+        }
+
+        if positions.is_empty() {
+            let source = fs::read_to_string(path)
+                .map_err(|err| format!("Error reading file '{}': {}", path.display(), err))?;
+            positions = Lexer::scan_file(&source, path)
+                .map_err(|err| format!("Lexer error: {}", err))?
+                .token_list
+                .iter()
+                .map(|source_token| source_token.position)
+                .collect();
+        }
+        positions.sort_by_key(|position| (position.line, position.column));
+        Ok(positions)
+    }
+
+    fn stack_trace(&self) -> Value {
+        let frames: Vec<Value> = match self.state {
+            State::Stopped => self
+                .interpreter
+                .current_stack_trace()
+                .into_iter()
+                .enumerate()
+                .map(|(id, frame)| {
+                    let mut stack_frame = json!({
+                        "id": id,
+                        "name": frame.name,
+                        "line": frame.position.line,
+                        "column": frame.position.column,
+                    });
+                    if let Some(file) = &frame.file {
+                        stack_frame["source"] = json!({
+                            "name": file.file_name().map(|name| name.to_string_lossy()),
+                            "path": file.to_string_lossy(),
+                        });
+                    }
+                    stack_frame
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    /// The operand stack, then each scope from the innermost out. Scopes are not tied to
+    /// stack frames in Gnarly, so every frame has the same ones.
+    fn scopes(&self) -> Value {
+        let mut scopes = vec![json!({
+            "name": "Operand stack",
+            "variablesReference": OPERAND_STACK_REFERENCE,
+            "expensive": false,
+        })];
+        for depth in (0..self.interpreter.context.scope_depth()).rev() {
+            let name = if depth == 0 {
+                "Global".to_string()
+            } else {
+                format!("Scope {}", depth)
+            };
+            scopes.push(json!({
+                "name": name,
+                "variablesReference": SCOPE_REFERENCE_BASE + depth,
+                "expensive": false,
+            }));
+        }
+        json!({ "scopes": scopes })
+    }
+
+    /// `{ variablesReference }`
+    fn variables(&mut self, arguments: &Value) -> Result<Value, String> {
+        let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+        let context = &self.interpreter.context;
+
+        let items: Vec<(String, Operand)> = if reference == OPERAND_STACK_REFERENCE {
+            // Top of the stack first
+            let operand_stack = context.current_scope_readonly().get_operand_stack();
+            operand_stack
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, operand)| (format!("[{}]", i), operand.clone()))
+                .collect()
+        } else if reference >= VALUE_REFERENCE_BASE {
+            match self.values.get(reference - VALUE_REFERENCE_BASE) {
+                Some(Operand::Scope(scope)) => {
+                    let mut items = sorted_variables(scope.get_variable_state());
+                    items.extend(
+                        scope
+                            .get_operand_stack()
+                            .iter()
+                            .enumerate()
+                            .map(|(i, operand)| (format!("[{}]", i), operand.clone())),
+                    );
+                    items
+                }
+                _ => return Err(format!("Unknown variables reference: {}", reference)),
+            }
+        } else {
+            match context
+                .get_scopes()
+                .get(reference.wrapping_sub(SCOPE_REFERENCE_BASE))
+            {
+                Some(scope) => sorted_variables(scope.get_variable_state()),
+                None => return Err(format!("Unknown variables reference: {}", reference)),
+            }
+        };
+
+        let variables: Vec<Value> = items
+            .into_iter()
+            .map(|(name, operand)| {
+                let (value, reference) = self.describe(operand);
+                json!({ "name": name, "value": value, "variablesReference": reference })
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// `{ expression }`
+    /// Runs the expression where the program is stopped, showing what it leaves on the stack.
+    fn evaluate(&mut self, arguments: &Value) -> Result<Value, String> {
+        if matches!(self.state, State::Running(_)) {
+            return Err("Cannot evaluate while the program is running".to_string());
+        }
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+        let tokens = Lexer::scan(expression)
+            .map_err(|err| format!("Lexer error: {}", err))?
+            .token_list;
+        let mut operands = self
+            .interpreter
+            .evaluate(tokens)
+            .map_err(|err| err.to_string())?;
+
+        let (result, reference) = match operands.len() {
+            1 => self.describe(operands.remove(0)),
+            _ => {
+                let context = &self.interpreter.context;
+                let values: Vec<String> = operands
+                    .iter()
+                    .map(|operand| context.operand_display(operand))
+                    .collect();
+                (values.join(" "), 0)
+            }
+        };
+        Ok(json!({ "result": result, "variablesReference": reference }))
+    }
+
+    /// How a value is shown, and the reference for expanding it if it is an array or object.
+    fn describe(&mut self, operand: Operand) -> (String, usize) {
+        let value = self.interpreter.context.operand_display(&operand);
+        match operand {
+            Operand::Scope(_) => {
+                self.values.push(operand);
+                (value, VALUE_REFERENCE_BASE + self.values.len() - 1)
+            }
+            _ => (value, 0),
+        }
+    }
+}
+
+fn sorted_variables(variables: &HashMap<String, Operand>) -> Vec<(String, Operand)> {
+    let mut variables: Vec<(String, Operand)> = variables
+        .iter()
+        .map(|(name, operand)| (format!("${}", name), operand.clone()))
+        .collect();
+    variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+    variables
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
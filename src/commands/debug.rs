@@ -0,0 +1,470 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::Args;
+use gnarly_interpreter::{
+    interpreter::{
+        ExecutionStatus, Interpreter, InterpreterConfig,
+        error::{ErrorKind, RuntimeError},
+    },
+    lexer::{Lexer, SourceToken, Token},
+};
+use rustyline::{DefaultEditor, error::ReadlineError};
+
+use crate::commands::repl::{print_stack, print_variables};
+
+// @NOTE The debugger drives the interpreter with `Interpreter::step`, checking breakpoints
+// before every token. A line breakpoint only stops when execution arrives at the line, not
+// for every token on it, unless the line is re-entered from another block.
+
+const PROMPT: &str = "(debug) ";
+
+const COMMANDS: &[(&str, &str)] = &[
+    ("step, s", "Execute the next token, stepping into blocks"),
+    (
+        "next, n",
+        "Execute the next token, running any block it calls to the end",
+    ),
+    ("continue, c", "Run until the next breakpoint"),
+    (
+        "break, b <where> [if <code>]",
+        "Stop at a line, file:line or operator, optionally only if code leaves a truthy value",
+    ),
+    ("delete, d <id>", "Remove a breakpoint"),
+    ("breakpoints", "List breakpoints"),
+    ("stack", "Show the operands on the stack"),
+    ("vars", "Show the variables in every scope"),
+    ("backtrace, bt", "Show the blocks being executed"),
+    (
+        "eval, p <code>",
+        "Run code where the program is paused and show what it leaves on the stack",
+    ),
+    ("help", "Show this list of commands"),
+    ("quit, q", "Stop debugging"),
+];
+
+#[derive(Args)]
+pub struct DebugArgs {
+    #[arg(help = "Path to the program to debug")]
+    pub file: PathBuf,
+}
+
+enum Target {
+    /// Line in any file, or only in files whose path ends with `file`
+    Line {
+        file: Option<String>,
+        line: usize,
+    },
+    Operator(String),
+}
+
+struct Breakpoint {
+    id: usize,
+    target: Target,
+    /// Source and tokens of the code that decides whether to stop
+    condition: Option<(String, Vec<SourceToken>)>,
+}
+
+/// Where a token is, and how deeply nested the block executing it is.
+#[derive(PartialEq)]
+struct StopPoint {
+    file: Option<Arc<Path>>,
+    line: usize,
+    call_depth: usize,
+}
+
+struct Debugger {
+    interpreter: Interpreter,
+    /// Whether the program has not finished (or failed) yet
+    running: bool,
+    /// Exit code of the program, once it has failed or called `exit`
+    exit_code: i32,
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_id: usize,
+    /// Lines of source files, for showing where the program is paused
+    sources: HashMap<Arc<Path>, Option<Vec<String>>>,
+}
+
+/// Debug a program interactively, returning the exit code the program would have had.
+pub fn run(
+    args: DebugArgs,
+    tokens: Vec<SourceToken>,
+    config: InterpreterConfig,
+) -> Result<i32, String> {
+    let mut editor =
+        DefaultEditor::new().map_err(|err| format!("Error starting debugger: {}", err))?;
+    let mut debugger = Debugger {
+        interpreter: Interpreter::new(config),
+        running: true,
+        exit_code: 0,
+        breakpoints: Vec::new(),
+        next_breakpoint_id: 1,
+        sources: HashMap::new(),
+    };
+
+    println!("Debugging '{}'", args.file.display());
+    println!("Type 'help' for a list of commands");
+    // Stop before the first token
+    let result = debugger.interpreter.run_paused(tokens);
+    debugger.handle_result(result);
+    debugger.show_location();
+
+    let mut last_command = String::new();
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(format!("Error reading input: {}", err)),
+        };
+
+        // An empty line repeats the previous command e.g. to keep stepping
+        let input = match line.trim() {
+            "" => last_command.clone(),
+            input => {
+                let _ = editor.add_history_entry(input);
+                input.to_string()
+            }
+        };
+        if input.is_empty() {
+            continue;
+        }
+        if matches!(input.as_str(), "quit" | "q") {
+            break;
+        }
+        debugger.run_command(&input);
+        last_command = input;
+    }
+
+    Ok(debugger.exit_code)
+}
+
+impl Debugger {
+    fn run_command(&mut self, input: &str) {
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+
+        match command {
+            "step" | "s" => self.step(),
+            "next" | "n" => self.step_over(),
+            "continue" | "c" => self.continue_to_breakpoint(),
+            "break" | "b" if !argument.is_empty() => self.add_breakpoint(argument),
+            "break" | "b" => eprintln!("Usage: break <line|file:line|operator> [if <code>]"),
+            "delete" | "d" => self.delete_breakpoint(argument),
+            "breakpoints" => self.print_breakpoints(),
+            "stack" => print_stack(&self.interpreter.context),
+            "vars" => print_variables(&self.interpreter.context),
+            "backtrace" | "bt" => self.print_backtrace(),
+            "eval" | "p" if !argument.is_empty() => self.evaluate(argument),
+            "eval" | "p" => eprintln!("Usage: eval <code>"),
+            "help" => {
+                for (command, description) in COMMANDS {
+                    println!("  {:<30} {}", command, description);
+                }
+            }
+            _ => eprintln!(
+                "Unknown command: {} (type 'help' for a list of commands)",
+                command
+            ),
+        }
+    }
+
+    fn step(&mut self) {
+        if self.execute_next_token() {
+            self.show_location();
+        }
+    }
+
+    fn step_over(&mut self) {
+        let call_depth = self.interpreter.call_depth();
+        let mut previous = self.stop_point();
+        if !self.execute_next_token() {
+            return;
+        }
+        while self.running && self.interpreter.call_depth() > call_depth {
+            if self.stop_at_breakpoint(previous.as_ref()) {
+                return;
+            }
+            previous = self.stop_point();
+            self.execute_next_token();
+        }
+        if self.running {
+            self.show_location();
+        }
+    }
+
+    fn continue_to_breakpoint(&mut self) {
+        loop {
+            let previous = self.stop_point();
+            if !self.execute_next_token() || !self.running {
+                return;
+            }
+            if self.stop_at_breakpoint(previous.as_ref()) {
+                return;
+            }
+        }
+    }
+
+    /// Execute one token, returning false if the program was not running.
+    fn execute_next_token(&mut self) -> bool {
+        if !self.running {
+            eprintln!("The program is not running");
+            return false;
+        }
+        let result = self.interpreter.step();
+        self.handle_result(result);
+        true
+    }
+
+    fn handle_result(&mut self, result: Result<ExecutionStatus, RuntimeError>) {
+        match result {
+            Ok(ExecutionStatus::Paused) => {}
+            Ok(ExecutionStatus::Completed) => {
+                self.running = false;
+                println!("Program finished");
+            }
+            // `exit` is not an error, so only its code is reported
+            Err(err) if matches!(err.kind, ErrorKind::Exit(_)) => {
+                self.running = false;
+                self.exit_code = crate::error_exit_code(&err);
+                println!("Program exited with code {}", self.exit_code);
+            }
+            Err(err) => {
+                self.running = false;
+                self.exit_code = crate::error_exit_code(&err);
+                eprintln!("Error: {}", err);
+                for frame in &err.trace {
+                    eprintln!("    at {}", frame);
+                }
+            }
+        }
+    }
+
+    /// Where the program is paused.
+    fn stop_point(&self) -> Option<StopPoint> {
+        self.interpreter.next_token().map(|source_token| StopPoint {
+            file: source_token.file.clone(),
+            line: source_token.position.line,
+            call_depth: self.interpreter.call_depth(),
+        })
+    }
+
+    /// Check the breakpoints against the next token, showing where the program is if one
+    /// of them is hit. `previous` is where the last token executed was.
+    fn stop_at_breakpoint(&mut self, previous: Option<&StopPoint>) -> bool {
+        let Some(source_token) = self.interpreter.next_token() else {
+            return false;
+        };
+        let arrived_at_line = self.stop_point().as_ref() != previous;
+
+        let mut hit = None;
+        for breakpoint in &self.breakpoints {
+            let matches = match &breakpoint.target {
+                Target::Line { file, line } => {
+                    arrived_at_line
+                        && source_token.position.line == *line
+                        && file.as_ref().is_none_or(|file| {
+                            source_token
+                                .file
+                                .as_ref()
+                                .is_some_and(|path| path.ends_with(file))
+                        })
+                }
+                Target::Operator(name) => {
+                    matches!(&source_token.token, Token::Operator(op) if op == name)
+                }
+            };
+            if matches {
+                hit = Some(breakpoint.id);
+                break;
+            }
+        }
+        let Some(id) = hit else {
+            return false;
+        };
+
+        let condition = self
+            .breakpoints
+            .iter()
+            .find(|breakpoint| breakpoint.id == id)
+            .and_then(|breakpoint| breakpoint.condition.clone());
+        if let Some((source, tokens)) = condition {
+            match self.interpreter.evaluate(tokens) {
+                Ok(operands) => {
+                    let truthy = match operands.last() {
+                        Some(operand) => self
+                            .interpreter
+                            .context
+                            .operand_is_truthy(operand)
+                            .unwrap_or(false),
+                        None => false,
+                    };
+                    if !truthy {
+                        return false;
+                    }
+                }
+                // Stop so that the condition can be fixed
+                Err(err) => eprintln!("Error in condition of breakpoint {}: {}", id, err),
+            }
+            println!("Breakpoint {} hit ({})", id, source);
+        } else {
+            println!("Breakpoint {} hit", id);
+        }
+        self.show_location();
+        true
+    }
+
+    fn add_breakpoint(&mut self, argument: &str) {
+        let (target, condition) = match argument.split_once(" if ") {
+            Some((target, condition)) => (target.trim(), Some(condition.trim())),
+            None => (argument, None),
+        };
+
+        let target = match target.rsplit_once(':') {
+            _ if target.chars().all(|ch| ch.is_ascii_digit()) => Target::Line {
+                file: None,
+                line: target.parse().unwrap_or(0),
+            },
+            Some((file, line))
+                if !line.is_empty() && line.chars().all(|ch| ch.is_ascii_digit()) =>
+            {
+                Target::Line {
+                    file: Some(file.to_string()),
+                    line: line.parse().unwrap_or(0),
+                }
+            }
+            _ => Target::Operator(target.to_string()),
+        };
+        let condition = match condition {
+            Some(condition) => match Lexer::scan(condition) {
+                Ok(lexer) => Some((condition.to_string(), lexer.token_list)),
+                Err(err) => {
+                    eprintln!("Lexer error in condition: {}", err);
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        let breakpoint = Breakpoint {
+            id,
+            target,
+            condition,
+        };
+        println!("Breakpoint {}: {}", id, describe(&breakpoint));
+        self.breakpoints.push(breakpoint);
+    }
+
+    fn delete_breakpoint(&mut self, argument: &str) {
+        let Ok(id) = argument.parse::<usize>() else {
+            eprintln!("Usage: delete <id>");
+            return;
+        };
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        if self.breakpoints.len() == count {
+            eprintln!("No breakpoint {}", id);
+        } else {
+            println!("Deleted breakpoint {}", id);
+        }
+    }
+
+    fn print_breakpoints(&self) {
+        if self.breakpoints.is_empty() {
+            println!("No breakpoints");
+        }
+        for breakpoint in &self.breakpoints {
+            println!("  {}: {}", breakpoint.id, describe(breakpoint));
+        }
+    }
+
+    fn print_backtrace(&self) {
+        if !self.running {
+            eprintln!("The program is not running");
+            return;
+        }
+        for frame in self.interpreter.current_stack_trace() {
+            println!("  at {}", frame);
+        }
+    }
+
+    fn evaluate(&mut self, code: &str) {
+        let tokens = match Lexer::scan(code) {
+            Ok(lexer) => lexer.token_list,
+            Err(err) => {
+                eprintln!("Lexer error: {}", err);
+                return;
+            }
+        };
+        match self.interpreter.evaluate(tokens) {
+            Ok(operands) => {
+                for operand in operands {
+                    println!("{}", self.interpreter.context.operand_display(&operand));
+                }
+            }
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    }
+
+    /// Show the line the program is paused at, with a marker under the next token.
+    fn show_location(&mut self) {
+        let Some(source_token) = self.interpreter.next_token() else {
+            return;
+        };
+        let position = source_token.position;
+        let Some(file) = source_token.file.clone() else {
+            println!(
+                "Paused at line {}, column {}",
+                position.line, position.column
+            );
+            return;
+        };
+        println!(
+            "Paused at {}, line {}, column {}",
+            file.display(),
+            position.line,
+            position.column
+        );
+
+        let lines = self.sources.entry(file.clone()).or_insert_with(|| {
+            fs::read_to_string(&file)
+                .ok()
+                .map(|source| source.lines().map(str::to_string).collect())
+        });
+        if let Some(line) = lines
+            .as_ref()
+            .and_then(|lines| lines.get(position.line.saturating_sub(1)))
+        {
+            let gutter = position.line.to_string();
+            println!("  {} | {}", gutter, line);
+            println!(
+                "  {} | {}^",
+                " ".repeat(gutter.len()),
+                " ".repeat(position.column.saturating_sub(1))
+            );
+        }
+    }
+}
+
+fn describe(breakpoint: &Breakpoint) -> String {
+    let target = match &breakpoint.target {
+        Target::Line {
+            file: Some(file),
+            line,
+        } => format!("{}, line {}", file, line),
+        Target::Line { file: None, line } => format!("line {}", line),
+        Target::Operator(name) => format!("operator '{}'", name),
+    };
+    match &breakpoint.condition {
+        Some((source, _)) => format!("{} if {}", target, source),
+        None => target,
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use gnarly_interpreter::lexer::{Position, SourceToken, Token};
+use serde_json::{Value, json};
+
+#[derive(Args)]
+pub struct LexArgs {
+    #[arg(help = "Path to the program to lex")]
+    pub file: PathBuf,
+}
+
+/// Print the tokens of a program as a JSON array, with the span of source code each one
+/// was read from.
+pub fn run(tokens: Vec<SourceToken>) -> Result<bool, String> {
+    let tokens: Vec<Value> = tokens
+        .iter()
+        .map(|source_token| {
+            let value = match &source_token.token {
+                Token::NumberLiteral(value) => json!(value),
+                Token::StringLiteral(value)
+                | Token::Operator(value)
+                | Token::VariableIdentifier(value) => json!(value),
+                _ => Value::Null,
+            };
+            json!({
+                "kind": source_token.token.kind(),
+                "value": value,
+                "text": source_token.token.to_string(),
+                "file": source_token.file.as_ref().map(|file| file.display().to_string()),
+                "span": {
+                    "start": position(source_token.position),
+                    "end": position(source_token.end),
+                },
+            })
+        })
+        .collect();
+
+    // One token per line is easier to read and diff than fully pretty-printed JSON
+    let lines: Vec<String> = tokens.iter().map(|token| format!("  {}", token)).collect();
+    println!("[\n{}\n]", lines.join(",\n"));
+    Ok(true)
+}
+
+fn position(position: Position) -> Value {
+    json!({ "line": position.line, "column": position.column })
+}
+
+/// `--dump-tokens`: every token on its own line, with where it is.
+pub fn print_tokens(tokens: &[SourceToken]) {
+    for source_token in tokens {
+        println!("{} {}", location(source_token), source_token.token);
+    }
+}
+
+/// `--dump-ast`: the tree formed by blocks and scopes, one node per line, indented by depth.
+/// Fails without printing anything if the brackets do not match, which would otherwise only
+/// fail when executed.
+pub fn print_tree(tokens: &[SourceToken]) -> Result<(), String> {
+    let mut lines = Vec::new();
+    // Closing bracket expected for each open block or scope
+    let mut open: Vec<(Token, &SourceToken)> = Vec::new();
+    for source_token in tokens {
+        let indent = "  ".repeat(open.len());
+        match &source_token.token {
+            Token::BlockStart => {
+                lines.push(format!("{}block ({})", indent, location(source_token)));
+                open.push((Token::BlockEnd, source_token));
+            }
+            Token::ScopeStart => {
+                lines.push(format!("{}scope ({})", indent, location(source_token)));
+                open.push((Token::ScopeEnd, source_token));
+            }
+            token @ (Token::BlockEnd | Token::ScopeEnd) => match open.pop() {
+                Some((expected, _)) if expected == *token => {}
+                _ => {
+                    return Err(format!(
+                        "Unexpected '{}' ({})",
+                        token,
+                        location(source_token)
+                    ));
+                }
+            },
+            token => lines.push(format!(
+                "{}{} {} ({})",
+                indent,
+                token.kind(),
+                token,
+                location(source_token)
+            )),
+        }
+    }
+
+    if let Some((expected, source_token)) = open.pop() {
+        return Err(format!(
+            "Expected '{}' to close '{}' ({})",
+            expected,
+            source_token.token,
+            location(source_token)
+        ));
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// e.g. `main.gnarly:3:5`
+pub fn location(source_token: &SourceToken) -> String {
+    let position = source_token.position;
+    match &source_token.file {
+        Some(file) => format!("{}:{}:{}", file.display(), position.line, position.column),
+        None => format!("{}:{}", position.line, position.column),
+    }
+}
@@ -0,0 +1,417 @@
+use std::{
+    env, fs, mem,
+    path::{Path, PathBuf},
+};
+
+use gnarly_interpreter::{
+    execution_context::ExecutionContext,
+    interpreter::{Interpreter, InterpreterConfig, builtin_operators, error::ErrorKind},
+    lexer::{Lexer, SourceToken},
+    preprocessor::Preprocessor,
+};
+use rustyline::{Editor, error::ReadlineError, history::DefaultHistory};
+
+use crate::commands::repl::helper::ReplHelper;
+
+mod helper;
+
+const PROMPT: &str = "gnarly> ";
+/// Shown while the input so far has unclosed brackets or strings
+const CONTINUATION_PROMPT: &str = "   ...> ";
+const HISTORY_FILE_NAME: &str = ".gnarly_history";
+
+const COMMANDS: &[(&str, &str)] = &[
+    (".help", "Show this list of commands"),
+    (".stack", "Show the operands on the stack"),
+    (".vars", "Show the variables in every scope"),
+    (
+        ".ops [prefix]",
+        "List operators, optionally only those starting with prefix",
+    ),
+    (".load <file>", "Run a file in this session"),
+    (
+        ".save <file>",
+        "Write everything run in this session to a file",
+    ),
+    (".reset", "Start over with a fresh interpreter"),
+    (".exit", "Quit (or press Ctrl-D)"),
+];
+
+/// State of the REPL that outlives a single input.
+struct Session {
+    config: InterpreterConfig,
+    interpreter: Interpreter,
+    /// Everything that has been run since the last reset, for `.save`
+    inputs: Vec<String>,
+    /// Set once the code run calls `exit`
+    exit_code: Option<i32>,
+}
+
+/// Returns the code to exit the process with.
+pub fn run(config: InterpreterConfig) -> i32 {
+    println!("Gnarly REPL v{}", env!("CARGO_PKG_VERSION"));
+    println!("Type '.help' for a list of commands, or '.exit' to quit");
+
+    let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::new() {
+        Ok(editor) => editor,
+        Err(err) => {
+            eprintln!("Error starting REPL: {}", err);
+            return 1;
+        }
+    };
+    let history_path = history_path();
+    if let Some(history_path) = &history_path {
+        // Nothing to load on the first run
+        let _ = editor.load_history(history_path);
+    }
+
+    let mut session = Session {
+        interpreter: Interpreter::new(config.clone()),
+        config,
+        inputs: Vec::new(),
+        exit_code: None,
+    };
+    editor.set_helper(Some(ReplHelper::new(&session.interpreter)));
+    // Lines typed so far of input that is not complete yet
+    let mut pending_input = String::new();
+
+    while session.exit_code.is_none() {
+        // Complete anything defined by the previous input
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(&session.interpreter);
+        }
+
+        let prompt = if pending_input.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if !pending_input.is_empty() {
+                    pending_input.push('\n');
+                    pending_input.push_str(&line);
+                    if is_incomplete(&pending_input) {
+                        continue;
+                    }
+                    let input = mem::take(&mut pending_input);
+                    let _ = editor.add_history_entry(&input);
+                    session.run_input(input);
+                    continue;
+                }
+
+                let input = line.trim();
+
+                // Skip empty lines
+                if input.is_empty() {
+                    continue;
+                }
+                let _ = editor.add_history_entry(input);
+
+                // Handle special commands
+                if input.starts_with('.') {
+                    if input == ".exit" {
+                        break;
+                    }
+                    session.run_command(input);
+                    continue;
+                }
+
+                // Keep reading until e.g. a `{` on this line is closed
+                if is_incomplete(&line) {
+                    pending_input = line;
+                    continue;
+                }
+
+                session.run_input(input.to_string());
+            }
+            // Ctrl-C only cancels the input being typed
+            Err(ReadlineError::Interrupted) => pending_input.clear(),
+            // Ctrl-D
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error reading input: {}", err);
+                break;
+            }
+        }
+    }
+
+    if let Some(history_path) = &history_path
+        && let Err(err) = editor.save_history(history_path)
+    {
+        eprintln!(
+            "Error saving history to '{}': {}",
+            history_path.display(),
+            err
+        );
+    }
+    println!("Have a gnarly day!");
+    session.exit_code.unwrap_or(0)
+}
+
+/// History is kept in `~/.gnarly_history`, if there is a home directory.
+fn history_path() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(HISTORY_FILE_NAME))
+}
+
+/// Whether input has a `{` or `[` that has not been closed yet, or ends inside a string.
+fn is_incomplete(input: &str) -> bool {
+    let mut depth: isize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for ch in input.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    // Too many closing brackets is an error for the interpreter to report
+    in_string || depth > 0
+}
+
+/// Print the operand stack of the current scope, top first.
+pub fn print_stack(context: &ExecutionContext) {
+    let operand_stack = context.current_scope_readonly().get_operand_stack();
+    if operand_stack.is_empty() {
+        println!("Stack is empty");
+    }
+    for (i, operand) in operand_stack.iter().enumerate().rev() {
+        println!("  [{}] {}", i, context.operand_display(operand));
+    }
+}
+
+/// Print the variables of every scope, outermost first.
+pub fn print_variables(context: &ExecutionContext) {
+    let mut found_any = false;
+    for (depth, scope) in context.get_scopes().iter().enumerate() {
+        let mut variables: Vec<_> = scope.get_variable_state().iter().collect();
+        if variables.is_empty() {
+            continue;
+        }
+        variables.sort_by_key(|(name, _)| *name);
+
+        found_any = true;
+        if depth == 0 {
+            println!("Global scope:");
+        } else {
+            println!("Scope {}:", depth);
+        }
+        for (name, value) in variables {
+            println!("  ${} = {}", name, context.operand_display(value));
+        }
+    }
+    if !found_any {
+        println!("No variables are set");
+    }
+}
+
+impl Session {
+    /// Run some code, printing the newest operand if it pushed any.
+    fn run_input(&mut self, input: String) {
+        // Lex the input
+        let lexer_result = match Lexer::scan(&input) {
+            Ok(lexer) => lexer,
+            Err(err) => {
+                eprintln!("Lexer error: {}", err);
+                return;
+            }
+        };
+
+        self.inputs.push(input);
+        self.run_tokens(lexer_result.token_list);
+    }
+
+    fn run_tokens(&mut self, tokens: Vec<SourceToken>) {
+        let interpreter = &mut self.interpreter;
+        let operand_stack_size = interpreter
+            .context
+            .current_scope_readonly()
+            .get_operand_stack()
+            .len();
+        match interpreter.run(tokens) {
+            Ok(_) => {
+                // Print most recent operand, if any pushed to the stack
+                let operand_stack = interpreter
+                    .context
+                    .current_scope_readonly()
+                    .get_operand_stack();
+                if operand_stack.len() > operand_stack_size {
+                    println!(
+                        "{}",
+                        interpreter
+                            .context
+                            .operand_display(operand_stack.last().unwrap())
+                    )
+                }
+            }
+            Err(err) => match err.kind {
+                ErrorKind::Exit(code) => self.exit_code = Some(code),
+                _ => eprintln!("Error: {}", err),
+            },
+        }
+    }
+
+    /// Run a `.command`, e.g. `.load lib.gnarly`
+    fn run_command(&mut self, input: &str) {
+        let (command, argument) = match input.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input, ""),
+        };
+
+        match command {
+            ".help" => {
+                for (command, description) in COMMANDS {
+                    println!("  {:<16} {}", command, description);
+                }
+            }
+            ".stack" => print_stack(&self.interpreter.context),
+            ".vars" => print_variables(&self.interpreter.context),
+            ".ops" => self.print_operators(argument),
+            ".load" if !argument.is_empty() => self.load(Path::new(argument)),
+            ".save" if !argument.is_empty() => self.save(Path::new(argument)),
+            ".load" | ".save" => eprintln!("Usage: {} <file>", command),
+            ".reset" => {
+                self.interpreter = Interpreter::new(self.config.clone());
+                self.inputs.clear();
+                println!("Session reset");
+            }
+            _ => eprintln!(
+                "Unknown command: {} (type '.help' for a list of commands)",
+                command
+            ),
+        }
+    }
+
+    fn print_operators(&self, prefix: &str) {
+        for operator in builtin_operators() {
+            if operator.name.starts_with(prefix) {
+                println!("  {:<40} {}", operator.usage, operator.description);
+            }
+        }
+
+        let mut user_defined: Vec<&String> = self
+            .interpreter
+            .context
+            .get_definitions()
+            .keys()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        if !user_defined.is_empty() {
+            user_defined.sort();
+            println!("User-defined:");
+            for name in user_defined {
+                println!("  {}", name);
+            }
+        }
+    }
+
+    /// Run a file as if its contents had been typed in.
+    fn load(&mut self, path: &Path) {
+        if !self.allows_path(".load", path) {
+            return;
+        }
+        let preprocessed = match Preprocessor::new(&[], &self.interpreter.context.capabilities)
+            .process_file(path)
+        {
+            Ok(preprocessed) => preprocessed,
+            Err(err) => {
+                eprintln!("Preprocessor error: {}", err);
+                return;
+            }
+        };
+        let mut lexer_result = match Lexer::scan(&preprocessed.source) {
+            Ok(lexer) => lexer,
+            Err(err) => {
+                eprintln!("Lexer error: {}", err);
+                return;
+            }
+        };
+        preprocessed.map_tokens(&mut lexer_result.token_list);
+
+        // Saved sessions include the file rather than a copy of it
+        let include_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.inputs
+            .push(format!("@include \"{}\"", include_path.display()));
+        self.run_tokens(lexer_result.token_list);
+    }
+
+    fn save(&self, path: &Path) {
+        if !self.allows_path(".save", path) {
+            return;
+        }
+        let mut script = self.inputs.join("\n");
+        script.push('\n');
+        match fs::write(path, script) {
+            Ok(()) => println!(
+                "Saved {} input(s) to '{}'",
+                self.inputs.len(),
+                path.display()
+            ),
+            Err(err) => eprintln!("Error writing '{}': {}", path.display(), err),
+        }
+    }
+
+    /// `.load` and `.save` are limited to the files the session's programs could access.
+    fn allows_path(&self, command: &str, path: &Path) -> bool {
+        match self
+            .interpreter
+            .context
+            .require_path(command, &path.display().to_string())
+        {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_brackets_and_strings_are_incomplete() {
+        for input in [
+            "[ 1 2",
+            "{ 1 [ 2 ]",
+            "\"abc",
+            "\"two\nlines",
+            "\"quote \\\"",
+        ] {
+            assert!(is_incomplete(input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn closed_input_is_complete() {
+        for input in [
+            "1 2 +",
+            "[ 1 2 ] \"f\" def",
+            "{ 1 { 2 } }",
+            "\"abc\"",
+            // Brackets inside strings do not count
+            "\"{ [\" print",
+            "\"ends with \\\\\"",
+            // Too many closing brackets are for the interpreter to report
+            "1 ] }",
+        ] {
+            assert!(!is_incomplete(input), "{:?}", input);
+        }
+    }
+}
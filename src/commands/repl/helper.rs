@@ -0,0 +1,295 @@
+use std::borrow::Cow;
+
+use gnarly_interpreter::{
+    interpreter::{Interpreter, builtin_operators},
+    lexer::{Lexer, Token},
+};
+use rustyline::{
+    Context, Helper,
+    completion::{Completer, FilenameCompleter, Pair},
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    validate::Validator,
+};
+
+use super::COMMANDS;
+
+// ANSI colours for each kind of token
+const NUMBER_COLOUR: &str = "\x1b[33m";
+const STRING_COLOUR: &str = "\x1b[32m";
+const VARIABLE_COLOUR: &str = "\x1b[35m";
+const OPERATOR_COLOUR: &str = "\x1b[36m";
+const BRACKET_COLOUR: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Tab completion and syntax highlighting for the REPL.
+pub struct ReplHelper {
+    filename_completer: FilenameCompleter,
+    /// Builtin and user-defined operators, sorted
+    operators: Vec<String>,
+    /// Variables in any scope, sorted
+    variables: Vec<String>,
+}
+
+impl ReplHelper {
+    pub fn new(interpreter: &Interpreter) -> Self {
+        let mut helper = Self {
+            filename_completer: FilenameCompleter::new(),
+            operators: Vec::new(),
+            variables: Vec::new(),
+        };
+        helper.refresh(interpreter);
+        helper
+    }
+
+    /// Pick up operators and variables defined since the last input.
+    pub fn refresh(&mut self, interpreter: &Interpreter) {
+        let context = &interpreter.context;
+
+        self.operators = builtin_operators()
+            .iter()
+            .map(|operator| operator.name.to_string())
+            .chain(context.get_definitions().keys().cloned())
+            .collect();
+        self.operators.sort();
+        self.operators.dedup();
+
+        self.variables = context
+            .get_scopes()
+            .iter()
+            .flat_map(|scope| scope.get_variable_state().keys().cloned())
+            .collect();
+        self.variables.sort();
+        self.variables.dedup();
+    }
+
+    fn is_operator(&self, name: &str) -> bool {
+        self.operators
+            .binary_search_by(|operator| operator.as_str().cmp(name))
+            .is_ok()
+    }
+}
+
+/// Whether `pos` is inside a string literal.
+fn in_string(line: &str, pos: usize) -> bool {
+    let mut in_string = false;
+    let mut escaped = false;
+    for ch in line[..pos].chars() {
+        if escaped {
+            escaped = false;
+        } else if in_string && ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            in_string = !in_string;
+        }
+    }
+    in_string
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if line.starts_with(".load ") {
+            return self.filename_completer.complete(line, pos, ctx);
+        }
+        if in_string(line, pos) {
+            return Ok((pos, Vec::new()));
+        }
+
+        let start = line[..pos]
+            .rfind(char::is_whitespace)
+            .map_or(0, |index| index + 1);
+        let word = &line[start..pos];
+        let candidate = |name: String| Pair {
+            display: name.clone(),
+            replacement: name,
+        };
+
+        let candidates = if start == 0 && word.starts_with('.') {
+            COMMANDS
+                .iter()
+                .filter_map(|(usage, _)| usage.split_whitespace().next())
+                .filter(|command| command.starts_with(word))
+                .map(|command| candidate(command.to_string()))
+                .collect()
+        } else if let Some(prefix) = word.strip_prefix('$') {
+            self.variables
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .map(|name| candidate(format!("${}", name)))
+                .collect()
+        } else if word.is_empty() {
+            Vec::new()
+        } else {
+            self.operators
+                .iter()
+                .filter(|name| name.starts_with(word))
+                .map(|name| candidate(name.clone()))
+                .collect()
+        };
+        Ok((start, candidates))
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if line.starts_with('.') {
+            return Cow::Borrowed(line);
+        }
+        // Leave input that doesn't lex alone, but colour what has been typed of e.g. a scope
+        let Ok(lexer) = Lexer::scan_incomplete(line) else {
+            return Cow::Borrowed(line);
+        };
+
+        // Tokens only know where they start, so each one is coloured up to the start of the
+        // next, minus any trailing whitespace (or anything the lexer skipped)
+        let line_starts: Vec<usize> = [0]
+            .into_iter()
+            .chain(line.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let offset_of = |line_number: usize, column: usize| {
+            let line_start = line_starts[line_number - 1];
+            line[line_start..]
+                .char_indices()
+                .nth(column - 1)
+                .map_or(line.len(), |(index, _)| line_start + index)
+        };
+        let starts: Vec<usize> = lexer
+            .token_list
+            .iter()
+            .map(|source_token| offset_of(source_token.position.line, source_token.position.column))
+            .collect();
+
+        let mut highlighted = String::with_capacity(line.len() * 2);
+        highlighted.push_str(&line[..starts.first().copied().unwrap_or(line.len())]);
+        for (i, source_token) in lexer.token_list.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(line.len());
+            let segment = &line[starts[i]..end];
+            // Only strings can contain whitespace
+            let text = match source_token.token {
+                Token::StringLiteral(_) => segment.trim_end(),
+                _ => segment
+                    .split(char::is_whitespace)
+                    .next()
+                    .unwrap_or_default(),
+            };
+            let colour = match &source_token.token {
+                Token::NumberLiteral(_) => Some(NUMBER_COLOUR),
+                Token::StringLiteral(_) => Some(STRING_COLOUR),
+                Token::VariableIdentifier(_) => Some(VARIABLE_COLOUR),
+                Token::Operator(op) if self.is_operator(op) => Some(OPERATOR_COLOUR),
+                Token::Operator(_) => None,
+                Token::ScopeStart | Token::ScopeEnd | Token::BlockStart | Token::BlockEnd => {
+                    Some(BRACKET_COLOUR)
+                }
+            };
+            match colour {
+                Some(colour) => {
+                    highlighted.push_str(colour);
+                    highlighted.push_str(text);
+                    highlighted.push_str(RESET);
+                }
+                None => highlighted.push_str(text),
+            }
+            highlighted.push_str(&segment[text.len()..]);
+        }
+        Cow::Owned(highlighted)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
+        // Any keystroke can change how the line lexes
+        true
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use gnarly_interpreter::interpreter::InterpreterConfig;
+
+    use super::*;
+
+    fn highlight(line: &str) -> String {
+        let helper = ReplHelper::new(&Interpreter::new(InterpreterConfig::default()));
+        helper.highlight(line, 0).into_owned()
+    }
+
+    fn coloured(colour: &str, text: &str) -> String {
+        format!("{}{}{}", colour, text, RESET)
+    }
+
+    #[test]
+    fn each_kind_of_token_has_its_colour() {
+        assert_eq!(
+            highlight("12.5 \"a b\" $name print"),
+            format!(
+                "{} {} {} {}",
+                coloured(NUMBER_COLOUR, "12.5"),
+                coloured(STRING_COLOUR, "\"a b\""),
+                coloured(VARIABLE_COLOUR, "$name"),
+                coloured(OPERATOR_COLOUR, "print"),
+            )
+        );
+        assert_eq!(
+            highlight("{ [ ] }"),
+            format!(
+                "{} {} {} {}",
+                coloured(BRACKET_COLOUR, "{"),
+                coloured(BRACKET_COLOUR, "["),
+                coloured(BRACKET_COLOUR, "]"),
+                coloured(BRACKET_COLOUR, "}"),
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_operators_and_commands_are_not_coloured() {
+        assert_eq!(
+            highlight("1  not_defined"),
+            format!("{}  not_defined", coloured(NUMBER_COLOUR, "1"))
+        );
+        assert_eq!(highlight(".stack 1"), ".stack 1");
+        // Input that does not lex is left as it is
+        assert_eq!(highlight("1 ?"), "1 ?");
+    }
+
+    #[test]
+    fn operators_defined_in_the_session_are_coloured() {
+        let mut interpreter = Interpreter::new(InterpreterConfig::default());
+        let mut helper = ReplHelper::new(&interpreter);
+        assert_eq!(helper.highlight("double", 0), "double");
+
+        let tokens = Lexer::scan("[ 2 * ] \"double\" def").unwrap().token_list;
+        interpreter.run(tokens).unwrap();
+        helper.refresh(&interpreter);
+        assert_eq!(
+            helper.highlight("double", 0),
+            coloured(OPERATOR_COLOUR, "double")
+        );
+    }
+
+    #[test]
+    fn incomplete_input_is_coloured_as_far_as_it_goes() {
+        assert_eq!(
+            highlight("{ \"abc"),
+            format!(
+                "{} {}",
+                coloured(BRACKET_COLOUR, "{"),
+                coloured(STRING_COLOUR, "\"abc")
+            )
+        );
+    }
+}
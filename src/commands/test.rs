@@ -0,0 +1,333 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use gnarly_interpreter::{
+    interpreter::{Interpreter, InterpreterConfig},
+    lexer::{SourceToken, Token},
+};
+
+// @NOTE Test files are either a single test (the whole file), or contain any number of
+// `test "name" { ... }` blocks. Everything outside of the blocks is run before each test,
+// e.g. to define operators. Each test gets a fresh interpreter.
+
+const TEST_FILE_SUFFIX: &str = "_test.gnarly";
+
+#[derive(Args)]
+pub struct TestArgs {
+    #[arg(help = "Test file, or directory to search for *_test.gnarly files [default: .]")]
+    path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Also write results as JUnit XML to FILE"
+    )]
+    junit: Option<PathBuf>,
+}
+
+struct TestCase {
+    name: String,
+    body: Vec<SourceToken>,
+}
+
+struct TestResult {
+    name: String,
+    duration: Duration,
+    /// Error message, if the test failed
+    failure: Option<String>,
+}
+
+struct FileResults {
+    path: PathBuf,
+    results: Vec<TestResult>,
+}
+
+/// Run every test under `args.path`, returning whether they all passed.
+pub fn run(args: TestArgs, symbols: &[String], config: InterpreterConfig) -> Result<bool, String> {
+    let path = args.path.unwrap_or_else(|| PathBuf::from("."));
+    let test_files = discover_test_files(&path)
+        .map_err(|err| format!("Error finding tests in '{}': {}", path.display(), err))?;
+    // Most likely a typo in the path, which should not look like a passing run
+    if test_files.is_empty() {
+        return Err(format!(
+            "No *{} files found in '{}'",
+            TEST_FILE_SUFFIX,
+            path.display()
+        ));
+    }
+
+    let started_at = Instant::now();
+    let mut all_results = Vec::new();
+    for test_file in test_files {
+        let results = run_test_file(&test_file, symbols, &config);
+        all_results.push(FileResults {
+            path: test_file,
+            results,
+        });
+    }
+    let duration = started_at.elapsed();
+
+    let failures: Vec<(&Path, &TestResult)> = all_results
+        .iter()
+        .flat_map(|file| {
+            file.results
+                .iter()
+                .filter(|result| result.failure.is_some())
+                .map(|result| (file.path.as_path(), result))
+        })
+        .collect();
+    let total: usize = all_results.iter().map(|file| file.results.len()).sum();
+
+    if !failures.is_empty() {
+        println!();
+        println!("failures:");
+        for (path, result) in &failures {
+            println!();
+            println!("---- {}: {} ----", path.display(), result.name);
+            println!("{}", result.failure.as_deref().unwrap_or_default());
+        }
+    }
+
+    println!();
+    println!(
+        "test result: {}. {} passed; {} failed; finished in {:.2?}",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        total - failures.len(),
+        failures.len(),
+        duration
+    );
+
+    if let Some(junit_path) = args.junit {
+        fs::write(&junit_path, junit_xml(&all_results, duration))
+            .map_err(|err| format!("Error writing '{}': {}", junit_path.display(), err))?;
+    }
+
+    Ok(failures.is_empty())
+}
+
+/// `path` itself if it is a file, otherwise all test files inside it (recursively), sorted.
+fn discover_test_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut test_files = Vec::new();
+    let mut directories = vec![path.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry_path = entry?.path();
+            let file_name = entry_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if file_name.starts_with('.') || file_name == "target" {
+                continue;
+            }
+            if entry_path.is_dir() {
+                directories.push(entry_path);
+            } else if file_name.ends_with(TEST_FILE_SUFFIX) {
+                test_files.push(entry_path);
+            }
+        }
+    }
+    test_files.sort();
+    Ok(test_files)
+}
+
+fn run_test_file(path: &Path, symbols: &[String], config: &InterpreterConfig) -> Vec<TestResult> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string());
+
+    // Same as running the file directly, so tests see what `@include` and `@define` produce
+    let parsed =
+        crate::read_program(path, symbols, &config.capabilities).and_then(split_test_cases);
+    let (prelude, test_cases) = match parsed {
+        Ok((prelude, test_cases)) if test_cases.is_empty() => (
+            Vec::new(),
+            vec![TestCase {
+                name: file_name,
+                body: prelude,
+            }],
+        ),
+        Ok(parsed) => parsed,
+        Err(err) => {
+            // Report the whole file as a single failing test
+            println!("test {} ... FAILED", file_name);
+            return vec![TestResult {
+                name: file_name,
+                duration: Duration::ZERO,
+                failure: Some(err),
+            }];
+        }
+    };
+
+    println!();
+    println!(
+        "running {} test{} from {}",
+        test_cases.len(),
+        if test_cases.len() == 1 { "" } else { "s" },
+        path.display()
+    );
+
+    let mut results = Vec::new();
+    for test_case in test_cases {
+        let mut tokens = prelude.clone();
+        tokens.extend(test_case.body);
+
+        let started_at = Instant::now();
+        let mut interpreter = Interpreter::new(config.clone());
+        let failure = interpreter.run(tokens).err().map(|err| {
+            let mut message = err.to_string();
+            for frame in &err.trace {
+                let _ = write!(message, "\n    at {}", frame);
+            }
+            message
+        });
+        let duration = started_at.elapsed();
+
+        println!(
+            "test {} ... {} ({:.2?})",
+            test_case.name,
+            if failure.is_some() { "FAILED" } else { "ok" },
+            duration
+        );
+        results.push(TestResult {
+            name: test_case.name,
+            duration,
+            failure,
+        });
+    }
+    results
+}
+
+/// Separate `test "name" { ... }` blocks from the rest of the file.
+fn split_test_cases(tokens: Vec<SourceToken>) -> Result<(Vec<SourceToken>, Vec<TestCase>), String> {
+    let mut prelude = Vec::new();
+    let mut test_cases = Vec::new();
+    let mut tokens = tokens.into_iter();
+
+    while let Some(source_token) = tokens.next() {
+        if !matches!(&source_token.token, Token::Operator(op) if op == "test") {
+            prelude.push(source_token);
+            continue;
+        }
+
+        let position = source_token.position;
+        let expected_test = || {
+            format!(
+                "Expected 'test \"name\" {{ ... }}' (line {}, column {})",
+                position.line, position.column
+            )
+        };
+        let name = match tokens.next().map(|source_token| source_token.token) {
+            Some(Token::StringLiteral(name)) => name,
+            _ => return Err(expected_test()),
+        };
+        if !matches!(
+            tokens.next().map(|source_token| source_token.token),
+            Some(Token::ScopeStart)
+        ) {
+            return Err(expected_test());
+        }
+
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let Some(source_token) = tokens.next() else {
+                return Err(format!(
+                    "Unterminated test \"{}\", expected '}}' (line {}, column {})",
+                    name, position.line, position.column
+                ));
+            };
+            match source_token.token {
+                Token::ScopeStart => depth += 1,
+                Token::ScopeEnd => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            body.push(source_token);
+        }
+        test_cases.push(TestCase { name, body });
+    }
+
+    Ok((prelude, test_cases))
+}
+
+fn junit_xml(all_results: &[FileResults], duration: Duration) -> String {
+    let total: usize = all_results.iter().map(|file| file.results.len()).sum();
+    let failures = |results: &[TestResult]| {
+        results
+            .iter()
+            .filter(|result| result.failure.is_some())
+            .count()
+    };
+    let total_failures: usize = all_results.iter().map(|file| failures(&file.results)).sum();
+
+    let mut xml = String::new();
+    let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        xml,
+        r#"<testsuites name="gnarly" tests="{}" failures="{}" time="{:.6}">"#,
+        total,
+        total_failures,
+        duration.as_secs_f64()
+    );
+    for file in all_results {
+        let suite_name = xml_escape(&file.path.display().to_string());
+        let suite_duration: Duration = file.results.iter().map(|result| result.duration).sum();
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" time="{:.6}">"#,
+            suite_name,
+            file.results.len(),
+            failures(&file.results),
+            suite_duration.as_secs_f64()
+        );
+        for result in &file.results {
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{}" time="{:.6}""#,
+                xml_escape(&result.name),
+                suite_name,
+                result.duration.as_secs_f64()
+            );
+            match &result.failure {
+                Some(failure) => {
+                    let summary = failure.lines().next().unwrap_or_default();
+                    let _ = writeln!(
+                        xml,
+                        r#"><failure message="{}">{}</failure></testcase>"#,
+                        xml_escape(summary),
+                        xml_escape(failure)
+                    );
+                }
+                None => {
+                    let _ = writeln!(xml, "/>");
+                }
+            }
+        }
+        let _ = writeln!(xml, "  </testsuite>");
+    }
+    let _ = writeln!(xml, "</testsuites>");
+    xml
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
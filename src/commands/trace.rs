@@ -0,0 +1,68 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use gnarly_interpreter::interpreter::trace::TraceEvent;
+use serde_json::json;
+
+use crate::commands::lex::location;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TraceFormat {
+    /// One line per token, indented by call depth
+    Human,
+    /// One JSON object per token, for diffing or processing with other tools
+    Json,
+}
+
+/// Tracer that writes every token to stderr, so that it does not mix with the program's output.
+pub fn tracer(format: TraceFormat) -> impl FnMut(&TraceEvent) {
+    move |event| {
+        let line = match format {
+            TraceFormat::Human => human_readable(event),
+            TraceFormat::Json => json_line(event),
+        };
+        // Tracing must not change how the program runs, so failing to write is ignored
+        let _ = writeln!(io::stderr().lock(), "{}", line);
+    }
+}
+
+/// e.g. `main.gnarly:3:5 + [1, 2] -> [3]`, followed by any variable writes or error.
+fn human_readable(event: &TraceEvent) -> String {
+    let indent = "  ".repeat(event.call_depth);
+    let mut line = format!(
+        "{}{} {} [{}] -> [{}]",
+        indent,
+        location(event.token),
+        event.token.token,
+        event.stack_before.join(", "),
+        event.stack_after.join(", ")
+    );
+    for (name, value) in event.variable_writes {
+        line.push_str(&format!("\n{}    ${} = {}", indent, name, value));
+    }
+    if let Some(err) = event.error {
+        line.push_str(&format!("\n{}    error: {}", indent, err));
+    }
+    line
+}
+
+fn json_line(event: &TraceEvent) -> String {
+    let position = event.token.position;
+    let writes: Vec<_> = event
+        .variable_writes
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect();
+    json!({
+        "file": event.token.file.as_ref().map(|file| file.display().to_string()),
+        "line": position.line,
+        "column": position.column,
+        "depth": event.call_depth,
+        "token": event.token.token.to_string(),
+        "stack_before": event.stack_before,
+        "stack_after": event.stack_after,
+        "writes": writes,
+        "error": event.error.map(|err| err.to_string()),
+    })
+    .to_string()
+}
@@ -1,15 +1,91 @@
-use crate::{execution_context::scope::Scope, interpreter::Operand};
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+};
 
+use regex::Regex;
+
+use crate::{
+    execution_context::{
+        capabilities::{Capabilities, Capability},
+        output::Output,
+        scope::Scope,
+    },
+    interpreter::{
+        Operand,
+        error::{ErrorKind, RuntimeError},
+        limits::Limit,
+    },
+    lexer::SourceToken,
+};
+
+pub mod capabilities;
+pub mod output;
 pub mod scope;
 
+/// Number of compiled regular expressions kept for re-use. Patterns built at runtime could
+/// otherwise fill the cache without limit.
+pub const REGEX_CACHE_SIZE: usize = 64;
+
 pub struct ExecutionContext {
     scopes: Vec<Scope>,
+    /// Recently used regular expressions, least recently used first
+    regex_cache: VecDeque<(String, Regex)>,
+    /// Operators defined by the script with `def`
+    definitions: HashMap<String, Vec<SourceToken>>,
+    pub capabilities: Capabilities,
+    pub output: Output,
+    /// Bytes of strings and arrays pushed since the last reset, for `ResourceLimits::max_memory`
+    allocated_bytes: usize,
+    /// `ResourceLimits::max_memory`, for operators to check before making a large allocation
+    memory_limit: Option<usize>,
+    /// Variables assigned since they were last taken, if they are being recorded for tracing
+    variable_writes: Option<Vec<(String, Operand)>>,
 }
 
 impl ExecutionContext {
-    pub fn new() -> Self {
+    pub fn new(capabilities: Capabilities) -> Self {
         Self {
             scopes: vec![Scope::new()],
+            regex_cache: VecDeque::new(),
+            definitions: HashMap::new(),
+            capabilities,
+            output: Output::default(),
+            allocated_bytes: 0,
+            memory_limit: None,
+            variable_writes: None,
+        }
+    }
+
+    /// Fail with a permission error if `operator` is not allowed to use `capability`.
+    pub fn require_capability(
+        &self,
+        operator: &str,
+        capability: Capability,
+    ) -> Result<(), RuntimeError> {
+        if self.capabilities.allows(capability) {
+            Ok(())
+        } else {
+            Err(RuntimeError::new(
+                ErrorKind::PermissionDenied,
+                format!(
+                    "'{}' requires the '{}' capability",
+                    operator,
+                    capability.name()
+                ),
+            ))
+        }
+    }
+
+    /// Fail with a permission error if `operator` is not allowed to access `path`.
+    pub fn require_path(&self, operator: &str, path: &str) -> Result<(), RuntimeError> {
+        if self.capabilities.allows_path(path) {
+            Ok(())
+        } else {
+            Err(RuntimeError::new(
+                ErrorKind::PermissionDenied,
+                format!("'{}' is not allowed to access '{}'", operator, path),
+            ))
         }
     }
 
@@ -27,6 +103,81 @@ impl ExecutionContext {
             .expect("Unexpected error: No scopes to pop")
     }
 
+    /// Every scope, outermost (global) first.
+    pub fn get_scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn scope_depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Number of operands on the operand stacks of every scope.
+    pub fn operand_count(&self) -> usize {
+        self.scopes
+            .iter()
+            .map(|scope| scope.get_operand_stack().len())
+            .sum()
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated_bytes
+    }
+
+    pub fn reset_allocated_bytes(&mut self) {
+        self.allocated_bytes = 0;
+    }
+
+    /// Put back the count from `allocated_bytes`, e.g. after running something in between.
+    pub fn set_allocated_bytes(&mut self, allocated_bytes: usize) {
+        self.allocated_bytes = allocated_bytes;
+    }
+
+    pub fn set_memory_limit(&mut self, memory_limit: Option<usize>) {
+        self.memory_limit = memory_limit;
+    }
+
+    /// Fail if allocating `bytes` more would exceed the memory limit. The interpreter checks
+    /// the limit after every token, which is too late to stop a single huge allocation, so
+    /// operators that grow data call this first.
+    pub fn reserve_memory(&self, operator: &str, bytes: usize) -> Result<(), RuntimeError> {
+        match self.memory_limit {
+            Some(max_memory) if self.allocated_bytes.saturating_add(bytes) > max_memory => {
+                Err(RuntimeError::new(
+                    ErrorKind::LimitExceeded(Limit::Memory),
+                    format!(
+                        "'{}' would allocate {} bytes, more than the {} allowed",
+                        operator, bytes, max_memory
+                    ),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// An empty string with room for `length` bytes, after checking them against the memory
+    /// limit. `None` is a length that overflowed. Without a limit, a length that cannot be
+    /// allocated would abort the process, so this fails with an error instead.
+    pub fn reserve_string(
+        &self,
+        operator: &str,
+        length: Option<usize>,
+    ) -> Result<String, RuntimeError> {
+        let too_large = || RuntimeError::from(format!("{}: Result is too large", operator));
+        let length = length.ok_or_else(too_large)?;
+        self.reserve_memory(operator, length)?;
+        let mut string = String::new();
+        string.try_reserve_exact(length).map_err(|_| too_large())?;
+        Ok(string)
+    }
+
+    /// Roll back to an earlier state, discarding any scopes opened since then and replacing
+    /// the operand stack of the scope that is then current.
+    pub fn restore_scopes(&mut self, scope_depth: usize, operand_stack: Vec<Operand>) {
+        self.scopes.truncate(scope_depth.max(1));
+        self.current_scope().set_operand_stack(operand_stack);
+    }
+
     pub fn current_scope(&mut self) -> &mut Scope {
         match self.scopes.last_mut() {
             Some(scope) => scope,
@@ -48,8 +199,10 @@ impl ExecutionContext {
         match operand {
             Operand::Number(value) => Ok(value.to_string()),
             Operand::String(value) => Ok(value.clone()),
+            Operand::Boolean(value) => Ok(value.to_string()),
+            Operand::Null => Ok("null".to_string()),
             Operand::Variable(name) => match self.get_variable(name) {
-                Some(inner) => self.operand_to_string(&inner),
+                Some(inner) => self.operand_to_string(inner),
                 None => Err(format!("Cannot stringify: Variable '{}' not found", name)),
             },
             Operand::Scope(scope) => {
@@ -61,14 +214,64 @@ impl ExecutionContext {
                     parts.push(self.operand_display(operand));
                 }
 
-                // Add variables as key-value pairs
-                let variables = scope.get_variable_state();
+                // Add variables as key-value pairs, sorted so that output is stable
+                let mut variables: Vec<_> = scope.get_variable_state().iter().collect();
+                variables.sort_by_key(|(name, _)| *name);
                 for (name, value) in variables {
                     parts.push(format!("{} = {}", name, self.operand_display(value)));
                 }
 
                 Ok(format!("{{ {} }}", parts.join(", ")))
             }
+            Operand::Block(_) => Ok(self.operand_display(operand)),
+        }
+    }
+
+    /// Convert an operand into a number.
+    /// e.g. `"12.5"` => `12.5`
+    /// e.g. `true` => `1`
+    pub fn operand_to_number(&self, operand: &Operand) -> Result<f64, String> {
+        match operand {
+            Operand::Number(value) => Ok(*value),
+            Operand::String(value) => value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("Cannot convert to number: \"{}\"", value)),
+            Operand::Boolean(value) => Ok(if *value { 1.0 } else { 0.0 }),
+            Operand::Variable(name) => match self.get_variable(name) {
+                Some(inner) => self.operand_to_number(inner),
+                None => Err(format!(
+                    "Cannot convert to number: Variable '{}' not found",
+                    name
+                )),
+            },
+            _ => Err(format!(
+                "Cannot convert to number: {}",
+                self.operand_display(operand)
+            )),
+        }
+    }
+
+    /// Whether an operand counts as `true` when used as a condition.
+    /// `false`, `null`, `0`, `NaN`, `""` and empty scopes are falsy, everything else is truthy.
+    /// @NOTE Any non-empty string is truthy, including `"false"` and `"0"`
+    pub fn operand_is_truthy(&self, operand: &Operand) -> Result<bool, String> {
+        match operand {
+            Operand::Number(value) => Ok(*value != 0.0 && !value.is_nan()),
+            Operand::String(value) => Ok(!value.is_empty()),
+            Operand::Boolean(value) => Ok(*value),
+            Operand::Null => Ok(false),
+            Operand::Variable(name) => match self.get_variable(name) {
+                Some(inner) => self.operand_is_truthy(inner),
+                None => Err(format!(
+                    "Cannot convert to boolean: Variable '{}' not found",
+                    name
+                )),
+            },
+            Operand::Scope(scope) => {
+                Ok(!scope.get_operand_stack().is_empty() || !scope.get_variable_state().is_empty())
+            }
+            Operand::Block(_) => Ok(true),
         }
     }
 
@@ -79,42 +282,117 @@ impl ExecutionContext {
         match operand {
             Operand::Number(value) => format!("{}", value),
             Operand::String(value) => format!("\"{}\"", value),
+            Operand::Boolean(value) => format!("{}", value),
+            Operand::Null => "null".to_string(),
             // Operand::Variable(name) => println!("${}", name),
             Operand::Variable(name) => match self.get_variable(name) {
-                Some(inner) => format!("${} ({})", name, self.operand_display(&inner)),
+                Some(inner) => format!("${} ({})", name, self.operand_display(inner)),
                 None => format!("${} (unset)", name),
             },
             Operand::Scope(scope) => {
-                format!("Scope({})", scope.get_operand_stack().len() + scope.get_variable_state().len())
+                format!(
+                    "Scope({})",
+                    scope.get_operand_stack().len() + scope.get_variable_state().len()
+                )
             }
+            Operand::Block(tokens) => format!("Block({})", tokens.len()),
         }
     }
 
     pub fn interpolate_string_variables(&self, input: &str) -> Result<String, String> {
         // @TODO Share regex with lexer code
-        let re = regex::Regex::new(r"\$([a-zA-Z_][a-zA-Z0-9_]*)").unwrap();
+        let re =
+            regex::Regex::new(r"\$([a-zA-Z_][a-zA-Z0-9_]*(\.[a-zA-Z_][a-zA-Z0-9_]*)*)").unwrap();
         let mut result = String::new();
         let mut last_end = 0;
         for cap in re.captures_iter(input) {
             if let Some(m) = cap.get(0) {
                 result.push_str(&input[last_end..m.start()]);
-                let var_name = &cap[1];
-                if let Some(val) = self.get_variable(&var_name.to_string()) {
-                    result.push_str(&self.operand_to_string(&val)?);
-                } else {
-                    return Err(format!(
-                        "Cannot interpolate: Variable '{}' not found",
-                        var_name
-                    ));
-                }
-                last_end = m.end();
+                // Variables of imported modules have a `.` in their name, but otherwise a `.`
+                // is just text after the variable e.g. "$name.txt"
+                let mut var_name = &cap[1];
+                let val = loop {
+                    if let Some(val) = self.get_variable(&var_name.to_string()) {
+                        break val;
+                    }
+                    match var_name.rfind('.') {
+                        Some(dot) => var_name = &var_name[..dot],
+                        None => {
+                            return Err(format!(
+                                "Cannot interpolate: Variable '{}' not found",
+                                var_name
+                            ));
+                        }
+                    }
+                };
+                result.push_str(&self.operand_to_string(val)?);
+                last_end = m.start() + 1 + var_name.len();
             }
         }
         result.push_str(&input[last_end..]);
         Ok(result)
     }
 
+    /// Compile a regular expression, re-using a previously compiled copy if the same
+    /// pattern has been seen recently (e.g. inside a loop).
+    pub fn get_regex(&mut self, pattern: &str) -> Result<Regex, String> {
+        if let Some(index) = self
+            .regex_cache
+            .iter()
+            .position(|(cached, _)| cached == pattern)
+        {
+            let entry = self.regex_cache.remove(index).unwrap();
+            let regex = entry.1.clone();
+            self.regex_cache.push_back(entry);
+            return Ok(regex);
+        }
+
+        let regex = Regex::new(pattern)
+            .map_err(|err| format!("Invalid regular expression '{}': {}", pattern, err))?;
+        if self.regex_cache.len() == REGEX_CACHE_SIZE {
+            self.regex_cache.pop_front();
+        }
+        self.regex_cache
+            .push_back((pattern.to_string(), regex.clone()));
+        Ok(regex)
+    }
+
+    /// Number of compiled regular expressions currently cached.
+    pub fn regex_cache_len(&self) -> usize {
+        self.regex_cache.len()
+    }
+
+    pub fn define_operator(&mut self, name: String, body: Vec<SourceToken>) {
+        self.definitions.insert(name, body);
+    }
+
+    pub fn get_definitions(&self) -> &HashMap<String, Vec<SourceToken>> {
+        &self.definitions
+    }
+
+    /// Body of a user-defined operator, if one has been defined with this name.
+    pub fn get_definition(&self, name: &str) -> Option<&Vec<SourceToken>> {
+        self.definitions.get(name)
+    }
+
+    /// Start (or stop) recording every variable assignment, see `take_variable_writes`.
+    pub fn record_variable_writes(&mut self, enabled: bool) {
+        self.variable_writes = enabled.then(Vec::new);
+    }
+
+    /// Variables assigned since the last call, in order, if they are being recorded.
+    pub fn take_variable_writes(&mut self) -> Vec<(String, Operand)> {
+        self.variable_writes
+            .as_mut()
+            .map(mem::take)
+            .unwrap_or_default()
+    }
+
     pub fn set_variable(&mut self, name: String, value: Operand) {
+        if let Some(writes) = &mut self.variable_writes {
+            writes.push((name.clone(), value.clone()));
+        }
+
         for scope in self.scopes.iter_mut().rev() {
             if scope.has_variable(name.clone()) {
                 scope.set_variable(name, value);
@@ -126,6 +404,11 @@ impl ExecutionContext {
         self.current_scope().set_variable(name, value);
     }
 
+    /// Set a variable in the outermost scope, whichever scope is current.
+    pub fn set_global_variable(&mut self, name: String, value: Operand) {
+        self.scopes[0].set_variable(name, value);
+    }
+
     pub fn get_variable(&self, name: &String) -> Option<&Operand> {
         for scope in self.scopes.iter().rev() {
             if scope.has_variable(name.clone()) {
@@ -136,6 +419,16 @@ impl ExecutionContext {
     }
 
     pub fn push_operand(&mut self, operand: Operand) {
+        // @NOTE Arrays only count their own elements, as any strings inside them
+        // were already counted when they were pushed
+        self.allocated_bytes += match &operand {
+            Operand::String(value) => value.len(),
+            Operand::Scope(scope) => {
+                (scope.get_operand_stack().len() + scope.get_variable_state().len())
+                    * std::mem::size_of::<Operand>()
+            }
+            _ => 0,
+        };
         self.current_scope().push_operand(operand);
     }
 
@@ -153,6 +446,20 @@ impl ExecutionContext {
         })
     }
 
+    pub fn pop_operand_scope(&mut self) -> Result<Scope, String> {
+        self._pop_operand_and_parse("Scope", true, |token| match token {
+            Operand::Scope(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn pop_operand_block(&mut self) -> Result<Vec<SourceToken>, String> {
+        self._pop_operand_and_parse("Block", true, |token| match token {
+            Operand::Block(value) => Some(value),
+            _ => None,
+        })
+    }
+
     pub fn pop_operand_variable_identifier(&mut self) -> Result<String, String> {
         self._pop_operand_and_parse("VariableIdentifier", false, |token| match token {
             Operand::Variable(value) => Some(value),
@@ -164,7 +471,18 @@ impl ExecutionContext {
         let token = self.current_scope().pop_operand();
         match token {
             Some(token) => Ok(token),
-            None => Err(format!("Token stack is empty!")),
+            None => Err("Token stack is empty!".to_string()),
+        }
+    }
+
+    /// Pop an operand, replacing a variable identifier with the variable's current value.
+    pub fn pop_operand_value(&mut self) -> Result<Operand, String> {
+        match self.pop_operand_any()? {
+            Operand::Variable(name) => match self.get_variable(&name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(format!("Variable '{}' not found", name)),
+            },
+            operand => Ok(operand),
         }
     }
 
@@ -197,7 +515,7 @@ impl ExecutionContext {
                     None => Err(maybe_err_message),
                 }
             }
-            None => Err(format!("Token stack is empty!")),
+            None => Err("Token stack is empty!".to_string()),
         }
     }
 }
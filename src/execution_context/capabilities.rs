@@ -0,0 +1,138 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Which parts of the filesystem a script may access.
+#[derive(Debug, Clone)]
+pub enum FilesystemAccess {
+    None,
+    All,
+    /// Only paths inside one of these directories (or the files themselves)
+    Paths(Vec<PathBuf>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Capability {
+    Stdout,
+    Stdin,
+    Env,
+    Process,
+    Time,
+    Random,
+}
+
+impl Capability {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Stdout => "stdout",
+            Capability::Stdin => "stdin",
+            Capability::Env => "env",
+            Capability::Process => "process",
+            Capability::Time => "time",
+            Capability::Random => "random",
+        }
+    }
+}
+
+/// Side effects that a script is allowed to perform. Operators check these before doing
+/// anything observable outside of the interpreter.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Writing to stdout and stderr
+    pub stdout: bool,
+    pub stdin: bool,
+    pub filesystem: FilesystemAccess,
+    // @NOTE No builtin operator reads the environment, runs processes, reads the clock or
+    // generates random numbers yet, but embedders can already declare what they allow
+    pub env: bool,
+    pub process: bool,
+    pub time: bool,
+    pub random: bool,
+}
+
+impl Capabilities {
+    /// Everything allowed. The command line opts into this for scripts the user runs
+    /// themselves; embedders should only use it for trusted code.
+    pub fn all() -> Self {
+        Self {
+            stdout: true,
+            stdin: true,
+            filesystem: FilesystemAccess::All,
+            env: true,
+            process: true,
+            time: true,
+            random: true,
+        }
+    }
+
+    /// Nothing allowed, for running untrusted code. Grant individual capabilities as needed.
+    /// This is the default, so forgetting to configure a sandbox does not open one up.
+    pub fn none() -> Self {
+        Self {
+            stdout: false,
+            stdin: false,
+            filesystem: FilesystemAccess::None,
+            env: false,
+            process: false,
+            time: false,
+            random: false,
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Stdout => self.stdout,
+            Capability::Stdin => self.stdin,
+            Capability::Env => self.env,
+            Capability::Process => self.process,
+            Capability::Time => self.time,
+            Capability::Random => self.random,
+        }
+    }
+
+    pub fn allows_path(&self, path: &str) -> bool {
+        match &self.filesystem {
+            FilesystemAccess::None => false,
+            FilesystemAccess::All => true,
+            FilesystemAccess::Paths(allowed) => {
+                let path = resolve_path(Path::new(path));
+                allowed
+                    .iter()
+                    .any(|allowed| path.starts_with(resolve_path(allowed)))
+            }
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Turn a path into an absolute path with symlinks and `..` resolved as far as possible,
+/// so that e.g. `allowed/../secret.txt` cannot escape an allowed directory.
+/// The file itself may not exist yet (e.g. `file.write`), in which case only its
+/// parent directory is resolved.
+fn resolve_path(path: &Path) -> PathBuf {
+    if let Ok(resolved) = path.canonicalize() {
+        return resolved;
+    }
+    if let (Some(parent), Some(file_name)) = (path.parent(), path.file_name())
+        && let Ok(parent) = parent.canonicalize()
+    {
+        return parent.join(file_name);
+    }
+
+    // Nothing on disk to resolve against, normalise lexically instead
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalised = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                normalised.pop();
+            }
+            Component::CurDir => {}
+            _ => normalised.push(component),
+        }
+    }
+    normalised
+}
@@ -0,0 +1,44 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+type Stream = Arc<Mutex<dyn Write + Send>>;
+
+/// Where scripts print to. Defaults to the process's stdout and stderr, but can be replaced
+/// when embedding e.g. to capture output, or when stdout is needed for something else.
+#[derive(Clone)]
+pub struct Output {
+    stdout: Stream,
+    stderr: Stream,
+}
+
+impl Output {
+    pub fn new(stdout: impl Write + Send + 'static, stderr: impl Write + Send + 'static) -> Self {
+        Self {
+            stdout: Arc::new(Mutex::new(stdout)),
+            stderr: Arc::new(Mutex::new(stderr)),
+        }
+    }
+
+    pub fn stdout(&self) -> MutexGuard<'_, dyn Write + Send + 'static> {
+        lock(&self.stdout)
+    }
+
+    pub fn stderr(&self) -> MutexGuard<'_, dyn Write + Send + 'static> {
+        lock(&self.stderr)
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::new(io::stdout(), io::stderr())
+    }
+}
+
+/// A panic while printing leaves nothing worse than a half-written line, so keep printing.
+fn lock(stream: &Stream) -> MutexGuard<'_, dyn Write + Send + 'static> {
+    stream
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
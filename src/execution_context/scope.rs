@@ -2,8 +2,7 @@ use std::collections::HashMap;
 
 use crate::interpreter::Operand;
 
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Scope {
     operand_stack: Vec<Operand>,
     variable_state: HashMap<String, Operand>,
@@ -17,6 +16,14 @@ impl Scope {
         }
     }
 
+    /// Create a scope containing only the given operands, i.e. an array.
+    pub fn from_operands(operands: Vec<Operand>) -> Scope {
+        Self {
+            operand_stack: operands,
+            variable_state: HashMap::new(),
+        }
+    }
+
     pub fn has_variable(&self, name: String) -> bool {
         self.variable_state.contains_key(&name)
     }
@@ -43,12 +50,14 @@ impl Scope {
         self.operand_stack.push(operand);
     }
 
+    pub fn set_operand_stack(&mut self, operand_stack: Vec<Operand>) {
+        self.operand_stack = operand_stack;
+    }
+
     pub fn get_operand_stack(&self) -> &Vec<Operand> {
         &self.operand_stack
     }
     pub fn get_variable_state(&self) -> &HashMap<String, Operand> {
         &self.variable_state
     }
-
-
 }
@@ -1,54 +1,861 @@
-use crate::{execution_context::{scope::Scope, ExecutionContext}, lexer::Token};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use crate::{
+    execution_context::{ExecutionContext, capabilities::Capabilities, scope::Scope},
+    interpreter::{
+        error::{ErrorKind, RuntimeError, StackFrame},
+        handle::ExecutionHandle,
+        limits::{Limit, ResourceLimits},
+        modules::ModuleLoader,
+        trace::{TraceEvent, Tracer},
+    },
+    lexer::{Lexer, Position, SourceToken, Span, Token},
+};
+
+pub mod error;
+pub mod handle;
+pub mod limits;
+mod modules;
 mod operators;
+pub mod trace;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Operand {
     Number(f64),
     String(String),
+    Boolean(bool),
+    Null,
     Variable(String),
     Scope(Scope),
+    /// Code that has not been executed yet e.g. `[ 1 2 + ]`
+    Block(Vec<SourceToken>),
+}
+
+/// Documentation for a builtin operator.
+#[derive(Debug, Clone, Copy)]
+pub struct OperatorHelp {
+    pub name: &'static str,
+    /// Example of the operands it takes e.g. `a b +`
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+/// Operators implemented by the interpreter itself, as they run blocks.
+const CONTROL_OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "try",
+        usage: "[ body ] [ handler ] try",
+        description: "Run body, running handler with the error if it fails",
+    },
+    OperatorHelp {
+        name: "assert.throws",
+        usage: "[ body ] assert.throws",
+        description: "Fail unless running body fails",
+    },
+    OperatorHelp {
+        name: "def",
+        usage: "[ body ] \"name\" def",
+        description: "Define an operator that runs body",
+    },
+    OperatorHelp {
+        name: "call",
+        usage: "[ body ] call",
+        description: "Run a block",
+    },
+    OperatorHelp {
+        name: "import",
+        usage: "\"path\" import",
+        description: "Run a file once and use its operators as file_name.operator",
+    },
+];
+
+/// Every builtin operator, sorted by name.
+pub fn builtin_operators() -> Vec<OperatorHelp> {
+    let mut all: Vec<OperatorHelp> = CONTROL_OPERATORS
+        .iter()
+        .chain(operators::all_operators())
+        .copied()
+        .collect();
+    all.sort_by_key(|operator| operator.name);
+    all
+}
+
+/// Options for embedding the interpreter, e.g. to sandbox untrusted scripts.
+#[derive(Debug, Clone, Default)]
+pub struct InterpreterConfig {
+    pub capabilities: Capabilities,
+    pub limits: ResourceLimits,
+    /// Directories to look for files in when they are not next to the file importing them
+    pub module_paths: Vec<PathBuf>,
+}
+
+/// Whether `Interpreter::run` (or `resume`) ran to completion or was suspended part-way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecutionStatus {
+    Completed,
+    Paused,
+}
+
+/// Unit of work in the interpreter's queue. Blocks are executed by queueing their tokens
+/// in front of the rest of the program, along with markers for any bookkeeping that needs
+/// to happen once they finish.
+enum Instruction {
+    Token(SourceToken),
+    /// End of the body of the `try` at this index in `try_frames`
+    EndTry(usize),
+    /// End of the block of the innermost frame in `call_stack`
+    EndFrame,
+}
+
+/// A block that is currently executing, for stack traces.
+struct CallFrame {
+    name: String,
+    call_site: Location,
+    span: Option<Span>,
+}
+
+/// Where a token is in the source code, including which file.
+#[derive(Clone)]
+struct Location {
+    position: Position,
+    file: Option<Arc<Path>>,
+}
+
+impl Location {
+    fn of(source_token: &SourceToken) -> Self {
+        Self {
+            position: source_token.position,
+            file: source_token.file.clone(),
+        }
+    }
+}
+
+/// A `try` (or `assert.throws`) whose body is currently executing.
+struct TryFrame {
+    /// Block to run if the body fails, or `None` for `assert.throws`, which instead fails
+    /// if the body succeeds
+    handler: Option<Vec<SourceToken>>,
+    location: Location,
+    /// State to roll back to if the body fails
+    call_depth: usize,
+    scope_depth: usize,
+    operand_stack: Vec<Operand>,
 }
 
 pub struct Interpreter {
     pub context: ExecutionContext,
+    limits: ResourceLimits,
+    handle: ExecutionHandle,
+    /// Instructions still to be executed. Only non-empty while paused.
+    pending: VecDeque<Instruction>,
+    try_frames: Vec<TryFrame>,
+    call_stack: Vec<CallFrame>,
+    modules: ModuleLoader,
+    /// Progress of the current run, carried over when pausing so that limits span the whole run
+    steps: u64,
+    elapsed: Duration,
+    /// When the run was last started or resumed, i.e. since when `elapsed` is out of date
+    resumed_at: Instant,
+    /// Path of the module imported under each namespace, to catch two modules with one name
+    namespaces: HashMap<String, PathBuf>,
+    tracer: Option<Tracer>,
 }
 
 impl Interpreter {
-    pub fn new() -> Self {
+    pub fn new(config: InterpreterConfig) -> Self {
+        let mut context = ExecutionContext::new(config.capabilities);
+        context.set_memory_limit(config.limits.max_memory);
         Self {
-            context: ExecutionContext::new(),
+            context,
+            limits: config.limits,
+            handle: ExecutionHandle::default(),
+            pending: VecDeque::new(),
+            try_frames: Vec::new(),
+            call_stack: Vec::new(),
+            modules: ModuleLoader::new(config.module_paths),
+            steps: 0,
+            elapsed: Duration::ZERO,
+            resumed_at: Instant::now(),
+            namespaces: HashMap::new(),
+            tracer: None,
+        }
+    }
+
+    /// Handle for cancelling or pausing this interpreter, e.g. from another thread.
+    pub fn handle(&self) -> ExecutionHandle {
+        self.handle.clone()
+    }
+
+    /// Call `tracer` after every token this interpreter executes. Files it imports are run
+    /// by another interpreter, so their tokens are not traced.
+    pub fn set_tracer(&mut self, tracer: impl FnMut(&TraceEvent) + Send + 'static) {
+        self.context.record_variable_writes(true);
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    pub fn is_paused(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn run(&mut self, token_stack: Vec<SourceToken>) -> Result<ExecutionStatus, RuntimeError> {
+        self.start(token_stack)?;
+        self.execute_pending(false)
+    }
+
+    /// Start a run without executing any of it, stopped before its first token as if it had
+    /// been paused there, e.g. to step through a program from the beginning. Continue it with
+    /// `resume` or `step`.
+    pub fn run_paused(
+        &mut self,
+        token_stack: Vec<SourceToken>,
+    ) -> Result<ExecutionStatus, RuntimeError> {
+        self.start(token_stack)?;
+        if self.is_paused() {
+            Ok(ExecutionStatus::Paused)
+        } else {
+            Ok(ExecutionStatus::Completed)
+        }
+    }
+
+    fn start(&mut self, token_stack: Vec<SourceToken>) -> Result<(), RuntimeError> {
+        if self.is_paused() {
+            return Err("Cannot start a new run while paused, call resume() first"
+                .to_string()
+                .into());
+        }
+
+        // Requests made before now were meant for an earlier run, e.g. a cancel that arrived
+        // just as the previous run finished
+        self.handle.clear_requests();
+        self.load(token_stack);
+        Ok(())
+    }
+
+    fn load(&mut self, token_stack: Vec<SourceToken>) {
+        self.pending = token_stack.into_iter().map(Instruction::Token).collect();
+        self.steps = 0;
+        self.elapsed = Duration::ZERO;
+        self.context.reset_allocated_bytes();
+    }
+
+    /// Continue a paused run from the token it was suspended at.
+    pub fn resume(&mut self) -> Result<ExecutionStatus, RuntimeError> {
+        if !self.is_paused() {
+            return Err("Cannot resume: Interpreter is not paused"
+                .to_string()
+                .into());
+        }
+
+        self.execute_pending(true)
+    }
+
+    /// Execute the next token of a paused run, then pause again before the one after it.
+    pub fn step(&mut self) -> Result<ExecutionStatus, RuntimeError> {
+        self.handle.pause();
+        let result = self.resume();
+        // Don't leave the request behind if the run finished before it could be taken
+        self.handle.take_pause_request();
+        result
+    }
+
+    /// Token a paused run will execute next.
+    pub fn next_token(&self) -> Option<&SourceToken> {
+        self.pending
+            .iter()
+            .find_map(|instruction| match instruction {
+                Instruction::Token(source_token) => Some(source_token),
+                _ => None,
+            })
+    }
+
+    /// Number of blocks (user-defined operators, `call`s and `try`s) currently executing.
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// Call stack at the token a paused run will execute next, innermost frame first.
+    pub fn current_stack_trace(&self) -> Vec<StackFrame> {
+        match self.next_token() {
+            Some(source_token) => self.stack_trace(Location::of(source_token)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Run a snippet in the current (possibly paused) context, e.g. for a debugger. It runs in
+    /// a scope of its own, so it can read and assign existing variables without disturbing the
+    /// operand stack. Returns the operands it leaves behind.
+    pub fn evaluate(&mut self, tokens: Vec<SourceToken>) -> Result<Vec<Operand>, RuntimeError> {
+        // The snippet must not close scopes of the paused run
+        let mut depth = 0;
+        for source_token in &tokens {
+            match source_token.token {
+                Token::ScopeStart => depth += 1,
+                Token::ScopeEnd if depth == 0 => {
+                    let mut err =
+                        RuntimeError::from("Unexpected '}' without matching '{'".to_string());
+                    err.position = Some(source_token.position);
+                    return Err(err);
+                }
+                Token::ScopeEnd => depth -= 1,
+                _ => {}
+            }
+        }
+
+        let pending = mem::take(&mut self.pending);
+        let try_frames = mem::take(&mut self.try_frames);
+        let call_stack = mem::take(&mut self.call_stack);
+        let (steps, elapsed) = (self.steps, self.elapsed);
+        let allocated_bytes = self.context.allocated_bytes();
+        let scope_depth = self.context.scope_depth();
+        self.context.push_new_scope();
+
+        // Not `run`, which would drop requests meant for the paused run. The snippet's
+        // allocations count on top of the run's, but are forgotten afterwards with its scope.
+        self.load(tokens);
+        self.context.set_allocated_bytes(allocated_bytes);
+        let mut result = self.execute_pending(false);
+        while let Ok(ExecutionStatus::Paused) = result {
+            result = self.resume();
+        }
+        // Any scopes the snippet left open are discarded along with its own
+        let operands = match self.context.get_scopes().get(scope_depth) {
+            Some(scope) if result.is_ok() => scope.get_operand_stack().clone(),
+            _ => Vec::new(),
+        };
+        let operand_stack = self.context.get_scopes()[scope_depth - 1]
+            .get_operand_stack()
+            .clone();
+        self.context.restore_scopes(scope_depth, operand_stack);
+
+        self.pending = pending;
+        self.try_frames = try_frames;
+        self.call_stack = call_stack;
+        (self.steps, self.elapsed) = (steps, elapsed);
+        self.context.set_allocated_bytes(allocated_bytes);
+        result.map(|_| operands)
+    }
+
+    fn execute_pending(&mut self, resuming: bool) -> Result<ExecutionStatus, RuntimeError> {
+        self.resumed_at = Instant::now();
+        let result = self.execute_pending_tokens(resuming);
+        self.elapsed += self.resumed_at.elapsed();
+
+        // Don't leave the interpreter half-way through a failed run
+        if result.is_err() {
+            self.pending.clear();
+            self.try_frames.clear();
+            self.call_stack.clear();
+        }
+        result
+    }
+
+    fn execute_pending_tokens(&mut self, resuming: bool) -> Result<ExecutionStatus, RuntimeError> {
+        // A resumed run always makes progress, so that pausing again before resuming
+        // steps through a single token
+        let mut can_pause = !resuming;
+
+        while let Some(instruction) = self.pending.pop_front() {
+            let source_token = match instruction {
+                Instruction::Token(source_token) => source_token,
+                Instruction::EndTry(_) => {
+                    // Body finished without an error
+                    if let Some(TryFrame {
+                        handler: None,
+                        location,
+                        ..
+                    }) = self.try_frames.pop()
+                    {
+                        let err = operators::assert::assertion_failed(
+                            "assert.throws: Expected block to throw an error".to_string(),
+                        );
+                        self.fail(err, location)?;
+                    }
+                    continue;
+                }
+                Instruction::EndFrame => {
+                    self.call_stack.pop();
+                    continue;
+                }
+            };
+
+            if self.handle.take_cancel_request() {
+                return Err(RuntimeError::new(
+                    ErrorKind::Cancelled,
+                    "Execution was cancelled".to_string(),
+                ));
+            }
+            if can_pause && self.handle.take_pause_request() {
+                self.pending.push_front(Instruction::Token(source_token));
+                return Ok(ExecutionStatus::Paused);
+            }
+            can_pause = true;
+
+            self.steps += 1;
+            if let Some(max_steps) = self.limits.max_steps
+                && self.steps > max_steps
+            {
+                return Err(limit_exceeded(
+                    Limit::Steps,
+                    format!("Executed more than {} tokens", max_steps),
+                ));
+            }
+
+            let location = Location::of(&source_token);
+            let traced = self.tracer.is_some().then(|| {
+                (
+                    source_token.clone(),
+                    self.call_stack.len(),
+                    self.displayed_stack(),
+                )
+            });
+            let result = self.execute_token(source_token);
+            if let Some((source_token, call_depth, stack_before)) = traced {
+                self.trace(
+                    &source_token,
+                    call_depth,
+                    &stack_before,
+                    result.as_ref().err(),
+                );
+            }
+            if let Err(err) = result {
+                self.fail(err, location)?;
+            }
+
+            self.check_limits()?;
         }
+        Ok(ExecutionStatus::Completed)
     }
 
-    pub fn run(&mut self, mut token_stack: Vec<Token>) -> Result<(), String> {
-        while token_stack.len() > 0 {
-            let token = token_stack.remove(0);
-            match token {
-                Token::Operator(op) => operators::execute_operator(&mut self.context, &op)?,
-                Token::NumberLiteral(value) => {
-                    self.context.push_operand(Operand::Number(value));
+    fn execute_token(&mut self, source_token: SourceToken) -> Result<(), RuntimeError> {
+        let location = Location::of(&source_token);
+        match source_token.token {
+            Token::Operator(op) if op == "try" => {
+                let handler = self.context.pop_operand_block()?;
+                self.execute_try(location, Some(handler))?;
+            }
+            Token::Operator(op) if op == "assert.throws" => self.execute_try(location, None)?,
+            Token::Operator(op) if op == "def" => self.execute_def()?,
+            Token::Operator(op) if op == "import" => self.execute_import(location)?,
+            Token::Operator(op) if op == "call" => {
+                // `[ body ] call`
+                let body = self.context.pop_operand_block()?;
+                self.enter_block(op, location, body);
+            }
+            Token::Operator(op) => match self.context.get_definition(&op) {
+                Some(body) => {
+                    let body = body.clone();
+                    self.enter_block(op, location, body);
                 }
-                Token::StringLiteral(value) => {
-                    let interpolated = self.context.interpolate_string_variables(&value)?;
-                    self.context.push_operand(Operand::String(interpolated));
+                None => operators::execute_operator(&mut self.context, &op)?,
+            },
+            Token::NumberLiteral(value) => {
+                self.context.push_operand(Operand::Number(value));
+            }
+            Token::StringLiteral(value) => {
+                let interpolated = self.context.interpolate_string_variables(&value)?;
+                self.context.push_operand(Operand::String(interpolated));
+            }
+            Token::VariableIdentifier(variable_name) => {
+                self.context.push_operand(Operand::Variable(variable_name));
+            }
+            Token::ScopeStart => {
+                self.context.push_new_scope();
+            }
+            Token::ScopeEnd => {
+                if self.context.scope_depth() == 1 {
+                    return Err("Unexpected '}' without matching '{'".to_string().into());
                 }
-                Token::VariableIdentifier(variable_name) => {
-                    self.context.push_operand(Operand::Variable(variable_name));
+                let scope = self.context.pop_scope();
+                self.context.push_operand(Operand::Scope(scope));
+            }
+            Token::BlockStart => {
+                let block = self.take_block()?;
+                self.context.push_operand(Operand::Block(block));
+            }
+            Token::BlockEnd => {
+                return Err("Unexpected ']' without matching '['".to_string().into());
+            }
+        }
+        Ok(())
+    }
+
+    fn trace(
+        &mut self,
+        source_token: &SourceToken,
+        call_depth: usize,
+        stack_before: &[String],
+        error: Option<&RuntimeError>,
+    ) {
+        let variable_writes: Vec<(String, String)> = self
+            .context
+            .take_variable_writes()
+            .into_iter()
+            .map(|(name, value)| (name, self.context.operand_display(&value)))
+            .collect();
+        let stack_after = self.displayed_stack();
+        if let Some(tracer) = &mut self.tracer {
+            tracer(&TraceEvent {
+                token: source_token,
+                call_depth,
+                stack_before,
+                stack_after: &stack_after,
+                variable_writes: &variable_writes,
+                error,
+            });
+        }
+    }
+
+    fn displayed_stack(&self) -> Vec<String> {
+        self.context
+            .current_scope_readonly()
+            .get_operand_stack()
+            .iter()
+            .map(|operand| self.context.operand_display(operand))
+            .collect()
+    }
+
+    /// Remove the tokens of a block from the queue, up to its matching `]`.
+    fn take_block(&mut self) -> Result<Vec<SourceToken>, RuntimeError> {
+        let mut block = Vec::new();
+        let mut depth = 1;
+        loop {
+            match self.pending.pop_front() {
+                Some(Instruction::Token(source_token)) => {
+                    match source_token.token {
+                        Token::BlockStart => depth += 1,
+                        Token::BlockEnd => {
+                            depth -= 1;
+                            if depth == 0 {
+                                return Ok(block);
+                            }
+                        }
+                        _ => {}
+                    }
+                    block.push(source_token);
                 }
-                Token::ScopeStart => {
-                    self.context.push_new_scope();
+                Some(marker) => {
+                    // Leave bookkeeping for `handle_error`
+                    self.pending.push_front(marker);
+                    break;
                 }
-                Token::ScopeEnd => {
-                    let scope = self.context.pop_scope();
-                    self.context.push_operand(Operand::Scope(scope));
+                None => break,
+            }
+        }
+        Err("Unterminated block, expected ']'".to_string().into())
+    }
+
+    /// `[ body ] [ handler ] try`
+    /// Runs `body`. If it fails, the operand stack and scopes are rolled back to how they were
+    /// before `try`, an error value is pushed, and `handler` is run.
+    ///
+    /// `[ body ] assert.throws`
+    /// Runs `body`, rolling back as above if it fails, and fails itself if it does not.
+    fn execute_try(
+        &mut self,
+        location: Location,
+        handler: Option<Vec<SourceToken>>,
+    ) -> Result<(), RuntimeError> {
+        let body = self.context.pop_operand_block()?;
+        let name = if handler.is_some() {
+            "try"
+        } else {
+            "assert.throws"
+        };
+
+        self.try_frames.push(TryFrame {
+            handler,
+            location: location.clone(),
+            call_depth: self.call_stack.len(),
+            scope_depth: self.context.scope_depth(),
+            operand_stack: self
+                .context
+                .current_scope_readonly()
+                .get_operand_stack()
+                .clone(),
+        });
+        self.pending
+            .push_front(Instruction::EndTry(self.try_frames.len() - 1));
+        self.enter_block(name.to_string(), location, body);
+        Ok(())
+    }
+
+    /// `[ body ] "name" def`
+    /// Defines an operator that runs `body` whenever it is used.
+    fn execute_def(&mut self) -> Result<(), RuntimeError> {
+        let name = self.context.pop_operand_string_literal()?;
+        let body = self.context.pop_operand_block()?;
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("def: Invalid operator name: \"{}\"", name).into());
+        }
+        if matches!(
+            name.as_str(),
+            "try" | "def" | "call" | "import" | "assert.throws"
+        ) {
+            return Err(format!("def: Cannot redefine '{}'", name).into());
+        }
+        self.context.define_operator(name, body);
+        Ok(())
+    }
+
+    /// `"lib/utils.gnarly" import`
+    /// Runs another file (only the first time it is imported), then makes the operators it
+    /// defines available under the file's name e.g. `utils.helper`. Variables the file sets
+    /// at its top level are kept, for those operators to use.
+    fn execute_import(&mut self, location: Location) -> Result<(), RuntimeError> {
+        let import_path = self.context.pop_operand_string_literal()?;
+        let path = self
+            .modules
+            .resolve(&import_path, location.file.as_deref())?;
+        self.context
+            .require_path("import", &path.display().to_string())?;
+
+        let module = match self.modules.get(&path) {
+            Some(module) => module.clone(),
+            None => self.load_module(&path, location)?,
+        };
+        let namespace = modules::namespace(&path);
+        match self.namespaces.get(&namespace) {
+            Some(existing) if *existing != path => {
+                return Err(format!(
+                    "import: Cannot import '{}' as '{}', as '{}' already is",
+                    path.display(),
+                    namespace,
+                    existing.display()
+                )
+                .into());
+            }
+            Some(_) => {}
+            None => {
+                self.namespaces.insert(namespace.clone(), path.clone());
+            }
+        }
+        let module = modules::namespaced(&namespace, &module);
+        for (name, body) in module.definitions {
+            self.context.define_operator(name, body);
+        }
+        // Importing the module again does not reset what its operators have changed
+        for (name, value) in module.variables {
+            if self.context.get_variable(&name).is_none() {
+                self.context.set_global_variable(name, value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run a module in its own interpreter, so that it cannot affect the importing file
+    /// except through the operators it defines.
+    fn load_module(
+        &mut self,
+        path: &Path,
+        location: Location,
+    ) -> Result<modules::Module, RuntimeError> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("import: Cannot read '{}': {}", path.display(), err))?;
+        let tokens = Lexer::scan_file(&source, path)
+            .map_err(|err| format!("import: Lexer error in '{}': {}", path.display(), err))?
+            .token_list;
+        self.modules.start_loading(path)?;
+
+        let mut module = Interpreter::new(InterpreterConfig {
+            capabilities: self.context.capabilities.clone(),
+            limits: self.limits.clone(),
+            module_paths: Vec::new(),
+        });
+        module.handle = self.handle.clone();
+        module.context.output = self.context.output.clone();
+        module.modules = mem::take(&mut self.modules);
+
+        // Imports always run to completion. A pause requested in the meantime applies
+        // once the import has finished.
+        // The handle is shared, so requests for this run must not be cleared as `run` would
+        let mut paused = false;
+        module.load(tokens);
+        // Limits cover the whole run, imports included. Time spent in the module is counted
+        // by this interpreter's clock as well, so only the other budgets need handing back.
+        module.steps = self.steps;
+        module.elapsed = self.elapsed + self.resumed_at.elapsed();
+        module
+            .context
+            .set_allocated_bytes(self.context.allocated_bytes());
+        let mut result = module.execute_pending(false);
+        while let Ok(ExecutionStatus::Paused) = result {
+            paused = true;
+            result = module.resume();
+        }
+        if paused {
+            self.handle.pause();
+        }
+        self.steps = module.steps;
+        self.context
+            .set_allocated_bytes(module.context.allocated_bytes());
+
+        self.modules = mem::take(&mut module.modules);
+        match result {
+            Ok(_) => {
+                let loaded = modules::Module {
+                    definitions: module.context.get_definitions().clone(),
+                    variables: module.context.get_scopes()[0].get_variable_state().clone(),
+                };
+                self.modules.finish_loading(Some(loaded.clone()));
+                Ok(loaded)
+            }
+            Err(mut err) => {
+                self.modules.finish_loading(None);
+                // Continue the module's trace into the importing file
+                if let Some(frame) = err.trace.last_mut() {
+                    frame.name = format!("<module {}>", modules::namespace(path));
                 }
+                err.trace.extend(self.stack_trace(location));
+                Err(err)
+            }
+        }
+    }
+
+    /// Record where an error happened, then hand it to the innermost `try`.
+    fn fail(&mut self, mut err: RuntimeError, location: Location) -> Result<(), RuntimeError> {
+        err.position.get_or_insert(location.position);
+        if err.trace.is_empty() {
+            err.trace = self.stack_trace(location);
+        }
+        self.handle_error(err)
+    }
+
+    /// Hand an error to the innermost `try`, or return it if nothing can handle it.
+    fn handle_error(&mut self, err: RuntimeError) -> Result<(), RuntimeError> {
+        if !err.is_catchable() {
+            return Err(err);
+        }
+        let Some(frame) = self.try_frames.pop() else {
+            return Err(err);
+        };
+
+        // Skip the rest of the body
+        let frame_index = self.try_frames.len();
+        while let Some(instruction) = self.pending.pop_front() {
+            if let Instruction::EndTry(index) = instruction
+                && index == frame_index
+            {
+                break;
             }
         }
+
+        self.call_stack.truncate(frame.call_depth);
+        self.context
+            .restore_scopes(frame.scope_depth, frame.operand_stack);
+        if let Some(handler) = frame.handler {
+            self.context.push_operand(error_to_operand(err));
+            self.enter_block("try".to_string(), frame.location, handler);
+        }
         Ok(())
     }
 
+    /// Queue a block's tokens to be executed next, in a new frame on the call stack.
+    fn enter_block(&mut self, name: String, call_site: Location, block: Vec<SourceToken>) {
+        self.call_stack.push(CallFrame {
+            name,
+            call_site,
+            span: Span::of(&block),
+        });
+        self.pending.push_front(Instruction::EndFrame);
+        for source_token in block.into_iter().rev() {
+            self.pending.push_front(Instruction::Token(source_token));
+        }
+    }
 
+    /// Frames from the innermost block out to the top level, for an error raised at `location`.
+    fn stack_trace(&self, location: Location) -> Vec<StackFrame> {
+        let mut trace = Vec::new();
+        let mut location = location;
+        for frame in self.call_stack.iter().rev() {
+            trace.push(StackFrame {
+                name: frame.name.clone(),
+                position: location.position,
+                file: location.file,
+                span: frame.span,
+            });
+            location = frame.call_site.clone();
+        }
+        trace.push(StackFrame {
+            name: "<main>".to_string(),
+            position: location.position,
+            file: location.file,
+            span: None,
+        });
+        trace
+    }
 
+    fn check_limits(&self) -> Result<(), RuntimeError> {
+        if let Some(max_stack_size) = self.limits.max_stack_size
+            && self.context.operand_count() > max_stack_size
+        {
+            return Err(limit_exceeded(
+                Limit::StackSize,
+                format!("More than {} operands on the stack", max_stack_size),
+            ));
+        }
+        if let Some(max_scope_depth) = self.limits.max_scope_depth
+            && self.context.scope_depth() > max_scope_depth
+        {
+            return Err(limit_exceeded(
+                Limit::ScopeDepth,
+                format!("Scopes nested more than {} deep", max_scope_depth),
+            ));
+        }
+        if let Some(max_memory) = self.limits.max_memory
+            && self.context.allocated_bytes() > max_memory
+        {
+            return Err(limit_exceeded(
+                Limit::Memory,
+                format!("Allocated more than {} bytes", max_memory),
+            ));
+        }
+        if let Some(max_duration) = self.limits.max_duration
+            && self.elapsed + self.resumed_at.elapsed() > max_duration
+        {
+            return Err(limit_exceeded(
+                Limit::Duration,
+                format!("Ran for longer than {:?}", max_duration),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Structured value describing an error, as passed to a `try` handler
+/// e.g. `{ kind = "thrown", message = "oops", position = { line = 1, column = 9 }, value = "oops" }`
+fn error_to_operand(err: RuntimeError) -> Operand {
+    let mut error = Scope::new();
+    error.set_variable(
+        "kind".to_string(),
+        Operand::String(err.kind.name().to_string()),
+    );
+    error.set_variable("message".to_string(), Operand::String(err.message));
+    let position = match err.position {
+        Some(position) => {
+            let mut scope = Scope::new();
+            scope.set_variable("line".to_string(), Operand::Number(position.line as f64));
+            scope.set_variable(
+                "column".to_string(),
+                Operand::Number(position.column as f64),
+            );
+            Operand::Scope(scope)
+        }
+        None => Operand::Null,
+    };
+    error.set_variable("position".to_string(), position);
+    error.set_variable(
+        "value".to_string(),
+        err.value.map_or(Operand::Null, |value| *value),
+    );
+    Operand::Scope(error)
+}
 
+fn limit_exceeded(limit: Limit, message: String) -> RuntimeError {
+    RuntimeError::new(ErrorKind::LimitExceeded(limit), message)
 }
@@ -0,0 +1,148 @@
+use std::{fmt, path::Path, sync::Arc};
+
+use crate::{
+    interpreter::{Operand, limits::Limit},
+    lexer::{Position, Span},
+};
+
+/// Category of a runtime error, so that embedders can tell e.g. a sandbox violation apart
+/// from a bug in the script itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// General error raised while executing a script e.g. a type mismatch
+    Runtime,
+    /// Script tried to use a capability that the interpreter configuration does not allow
+    PermissionDenied,
+    /// Script used more of a resource than the configured `ResourceLimits` allow
+    LimitExceeded(Limit),
+    /// Script was aborted through an `ExecutionHandle`
+    Cancelled,
+    /// Script raised an error itself with `throw`
+    Thrown,
+    /// An `assert` operator failed
+    AssertionFailed,
+    /// Script asked to stop with `exit`, and the given exit code
+    Exit(i32),
+}
+
+impl ErrorKind {
+    /// Name of the error kind as seen by scripts in a `try` handler
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::PermissionDenied => "permission_denied",
+            ErrorKind::LimitExceeded(_) => "limit_exceeded",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::Thrown => "thrown",
+            ErrorKind::AssertionFailed => "assertion_failed",
+            ErrorKind::Exit(_) => "exit",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Where the token that raised the error is in the source code, if known
+    pub position: Option<Position>,
+    /// Value passed to `throw`
+    pub value: Option<Box<Operand>>,
+    /// Call stack at the point the error was raised, innermost frame first
+    pub trace: Vec<StackFrame>,
+}
+
+/// One level of the call stack, i.e. a user-defined operator, a `call` or `try` block, or
+/// the top level of the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    /// Operator that entered the frame, or `<main>` for the top level
+    pub name: String,
+    /// Where execution was inside this frame: the failing token for the innermost frame,
+    /// and the call site of the next frame in for the rest
+    pub position: Position,
+    /// File that `position` is in, if known
+    pub file: Option<Arc<Path>>,
+    /// Source code of the block being executed, `None` for the top level
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (", self.name)?;
+        if let Some(file) = &self.file {
+            write!(f, "{}, ", file.display())?;
+        }
+        write!(
+            f,
+            "line {}, column {})",
+            self.position.line, self.position.column
+        )
+    }
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            position: None,
+            value: None,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Error raised by `throw`, carrying the thrown value.
+    pub fn thrown(value: Operand, message: String) -> Self {
+        Self {
+            value: Some(Box::new(value)),
+            ..Self::new(ErrorKind::Thrown, message)
+        }
+    }
+
+    /// Whether a script can handle this error with `try`. Limits and cancellation
+    /// are enforced by the host, so scripts must not be able to swallow them, and `exit`
+    /// always stops the program.
+    pub fn is_catchable(&self) -> bool {
+        !matches!(
+            self.kind,
+            ErrorKind::LimitExceeded(_) | ErrorKind::Cancelled | ErrorKind::Exit(_)
+        )
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Runtime, message)
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Details on any following lines go after the position e.g. for `assert.eq`
+        let (summary, details) = match self.message.split_once('\n') {
+            Some((summary, details)) => (summary, Some(details)),
+            None => (self.message.as_str(), None),
+        };
+
+        match self.kind {
+            ErrorKind::Runtime => write!(f, "{}", summary),
+            ErrorKind::PermissionDenied => write!(f, "Permission denied: {}", summary),
+            ErrorKind::Cancelled => write!(f, "Cancelled: {}", summary),
+            ErrorKind::LimitExceeded(limit) => {
+                write!(f, "Exceeded {} limit: {}", limit.name(), summary)
+            }
+            ErrorKind::Thrown => write!(f, "Uncaught error: {}", summary),
+            ErrorKind::AssertionFailed => write!(f, "Assertion failed: {}", summary),
+            ErrorKind::Exit(code) => write!(f, "Exited with code {}", code),
+        }?;
+
+        if let Some(position) = self.position {
+            write!(f, " (line {}, column {})", position.line, position.column)?;
+        }
+        if let Some(details) = details {
+            write!(f, "\n{}", details)?;
+        }
+        Ok(())
+    }
+}
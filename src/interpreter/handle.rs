@@ -0,0 +1,42 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Thread-safe handle for controlling a running `Interpreter` from elsewhere, e.g. when the
+/// user hits "cancel" in an embedding application. Requests are checked between tokens, and
+/// only apply to the run in progress: any left over are dropped when the next run starts.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionHandle {
+    cancel_requested: Arc<AtomicBool>,
+    pause_requested: Arc<AtomicBool>,
+}
+
+impl ExecutionHandle {
+    /// Abort the running script. `Interpreter::run` will fail with `ErrorKind::Cancelled`.
+    pub fn cancel(&self) {
+        self.cancel_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Suspend the running script at the next token boundary. `Interpreter::run` will return
+    /// `ExecutionStatus::Paused` and can be continued with `Interpreter::resume`.
+    pub fn pause(&self) {
+        self.pause_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Consume a pending cancellation request, if any.
+    pub(super) fn take_cancel_request(&self) -> bool {
+        self.cancel_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Consume a pending pause request, if any.
+    pub(super) fn take_pause_request(&self) -> bool {
+        self.pause_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Drop any pending requests.
+    pub(super) fn clear_requests(&self) {
+        self.cancel_requested.store(false, Ordering::SeqCst);
+        self.pause_requested.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+/// Caps on the resources a single call to `Interpreter::run` may use, so that a runaway
+/// script fails with an error instead of hanging or exhausting memory.
+/// `None` means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    /// Number of tokens executed
+    pub max_steps: Option<u64>,
+    /// Number of operands across the operand stacks of every scope
+    pub max_stack_size: Option<usize>,
+    /// Number of nested scopes, including the root scope
+    pub max_scope_depth: Option<usize>,
+    /// Total bytes of strings and arrays allocated. Operators that grow data check this
+    /// before allocating, everything else after each token.
+    pub max_memory: Option<usize>,
+    /// Wall-clock time, checked after each token. Reading from stdin is not interrupted.
+    pub max_duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Steps,
+    StackSize,
+    ScopeDepth,
+    Memory,
+    Duration,
+}
+
+impl Limit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Limit::Steps => "step",
+            Limit::StackSize => "stack size",
+            Limit::ScopeDepth => "scope depth",
+            Limit::Memory => "memory",
+            Limit::Duration => "time",
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use regex::{Captures, Regex};
+
+use crate::{
+    interpreter::Operand,
+    lexer::{SourceToken, Token},
+};
+
+/// Operators defined by a module, by their name inside the module.
+pub type Definitions = HashMap<String, Vec<SourceToken>>;
+
+/// What a module leaves behind once it has run.
+#[derive(Clone, Default)]
+pub struct Module {
+    pub definitions: Definitions,
+    /// Variables set at the top level of the module, which its operators may still use
+    pub variables: HashMap<String, Operand>,
+}
+
+/// Finds and caches the files loaded with `import`.
+#[derive(Default)]
+pub struct ModuleLoader {
+    /// Directories to search after the importing file's own directory
+    search_paths: Vec<PathBuf>,
+    /// Modules that have already been run, by canonical path
+    loaded: HashMap<PathBuf, Module>,
+    /// Modules that are currently being run, outermost first, for detecting cycles
+    loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Self {
+            search_paths,
+            ..Self::default()
+        }
+    }
+
+    /// Find the file for `import_path`, looking next to the importing file first (or in the
+    /// working directory if there is none) and then in each search path.
+    pub fn resolve(
+        &self,
+        import_path: &str,
+        importing_file: Option<&Path>,
+    ) -> Result<PathBuf, String> {
+        let import_path = Path::new(import_path);
+        let mut candidates = Vec::new();
+        if import_path.is_absolute() {
+            candidates.push(import_path.to_path_buf());
+        } else {
+            let base = importing_file
+                .and_then(Path::parent)
+                .unwrap_or_else(|| Path::new(""));
+            candidates.push(base.join(import_path));
+            for search_path in &self.search_paths {
+                candidates.push(search_path.join(import_path));
+            }
+        }
+
+        for candidate in &candidates {
+            if candidate.is_file() {
+                return candidate.canonicalize().map_err(|err| {
+                    format!("import: Cannot resolve '{}': {}", candidate.display(), err)
+                });
+            }
+        }
+
+        let searched: Vec<String> = candidates
+            .iter()
+            .map(|candidate| candidate.display().to_string())
+            .collect();
+        Err(format!(
+            "import: Cannot find module '{}' (searched: {})",
+            import_path.display(),
+            searched.join(", ")
+        ))
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Module> {
+        self.loaded.get(path)
+    }
+
+    /// Mark a module as being run, failing if that would mean it imports itself.
+    pub fn start_loading(&mut self, path: &Path) -> Result<(), String> {
+        if let Some(start) = self.loading.iter().position(|loading| loading == path) {
+            let cycle: Vec<String> = self.loading[start..]
+                .iter()
+                .chain([&path.to_path_buf()])
+                .map(|path| path.display().to_string())
+                .collect();
+            return Err(format!("import: Import cycle: {}", cycle.join(" -> ")));
+        }
+        self.loading.push(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Finish running the innermost module, caching it if it succeeded.
+    pub fn finish_loading(&mut self, module: Option<Module>) {
+        if let Some(path) = self.loading.pop()
+            && let Some(module) = module
+        {
+            self.loaded.insert(path, module);
+        }
+    }
+}
+
+/// Name that a module's definitions are exposed under e.g. `utils` for `lib/utils.gnarly`.
+pub fn namespace(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// A module's definitions and variables renamed to `namespace.name`, including where the
+/// definitions use them, so that they still work once imported.
+// @NOTE Scripts cannot write a `.` in a variable name, so the importing file cannot clash with
+// (or change) the module's variables, only the module's own operators can
+pub fn namespaced(namespace: &str, module: &Module) -> Module {
+    let rename = |name: &str| format!("{}.{}", namespace, name);
+    let interpolated_variable = Regex::new(r"\$([a-zA-Z_][a-zA-Z0-9_]*)").unwrap();
+    let rename_token = |token: &Token| match token {
+        Token::Operator(op) if module.definitions.contains_key(op) => Token::Operator(rename(op)),
+        Token::VariableIdentifier(name) if module.variables.contains_key(name) => {
+            Token::VariableIdentifier(rename(name))
+        }
+        Token::StringLiteral(value) => Token::StringLiteral(
+            interpolated_variable
+                .replace_all(value, |captures: &Captures| {
+                    if module.variables.contains_key(&captures[1]) {
+                        format!("${}", rename(&captures[1]))
+                    } else {
+                        captures[0].to_string()
+                    }
+                })
+                .into_owned(),
+        ),
+        token => token.clone(),
+    };
+
+    let definitions = module
+        .definitions
+        .iter()
+        .map(|(name, body)| {
+            let body = body
+                .iter()
+                .map(|source_token| SourceToken {
+                    token: rename_token(&source_token.token),
+                    ..source_token.clone()
+                })
+                .collect();
+            (rename(name), body)
+        })
+        .collect();
+    let variables = module
+        .variables
+        .iter()
+        .map(|(name, value)| (rename(name), value.clone()))
+        .collect();
+    Module {
+        definitions,
+        variables,
+    }
+}
@@ -1,11 +1,43 @@
-use crate::execution_context::ExecutionContext;
+use std::io::{BufRead, Error as IoError, ErrorKind as IoErrorKind};
 
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{OperatorHelp, error::RuntimeError},
+};
+
+pub mod assert;
+pub mod conversion;
+pub mod format;
+pub mod fs;
 pub mod general;
 pub mod io;
 pub mod math;
+pub mod regex;
 pub mod string;
+pub mod system;
+
+/// Documentation for the operators of every module.
+pub fn all_operators() -> impl Iterator<Item = &'static OperatorHelp> {
+    [
+        math::OPERATORS,
+        io::OPERATORS,
+        general::OPERATORS,
+        string::OPERATORS,
+        regex::OPERATORS,
+        conversion::OPERATORS,
+        format::OPERATORS,
+        fs::OPERATORS,
+        system::OPERATORS,
+        assert::OPERATORS,
+    ]
+    .into_iter()
+    .flatten()
+}
 
-pub fn execute_operator(context: &mut ExecutionContext, operator: &String) -> Result<(), String> {
+pub fn execute_operator(
+    context: &mut ExecutionContext,
+    operator: &String,
+) -> Result<(), RuntimeError> {
     if math::execute(context, operator)? {
         return Ok(());
     }
@@ -18,6 +50,52 @@ pub fn execute_operator(context: &mut ExecutionContext, operator: &String) -> Re
     if string::execute(context, operator)? {
         return Ok(());
     }
+    if regex::execute(context, operator)? {
+        return Ok(());
+    }
+    if conversion::execute(context, operator)? {
+        return Ok(());
+    }
+    if format::execute(context, operator)? {
+        return Ok(());
+    }
+    if fs::execute(context, operator)? {
+        return Ok(());
+    }
+    if system::execute(context, operator)? {
+        return Ok(());
+    }
+    if assert::execute(context, operator)? {
+        return Ok(());
+    }
+
+    Err(format!("Unknown operator: {}", operator).into())
+}
 
-    Err(format!("Unknown operator: {}", operator))
+/// Read the rest of `reader` as text for `operator`, a buffer at a time, so that it cannot
+/// grow past the memory limit. `io_error` describes a failure to read it.
+pub fn read_string(
+    context: &ExecutionContext,
+    operator: &str,
+    mut reader: impl BufRead,
+    io_error: impl Fn(IoError) -> String,
+) -> Result<String, RuntimeError> {
+    let mut bytes = Vec::new();
+    loop {
+        let buffer = reader.fill_buf().map_err(&io_error)?;
+        if buffer.is_empty() {
+            break;
+        }
+        context.reserve_memory(operator, bytes.len() + buffer.len())?;
+        bytes.extend_from_slice(buffer);
+        let length = buffer.len();
+        reader.consume(length);
+    }
+    String::from_utf8(bytes).map_err(|_| {
+        io_error(IoError::new(
+            IoErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        ))
+        .into()
+    })
 }
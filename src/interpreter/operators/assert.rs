@@ -0,0 +1,169 @@
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{
+        Operand, OperatorHelp,
+        error::{ErrorKind, RuntimeError},
+    },
+};
+
+// @NOTE `assert.throws` needs to run a block, so it is handled by the interpreter itself
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "assert",
+        usage: "value assert",
+        description: "Fail unless value is truthy",
+    },
+    OperatorHelp {
+        name: "assert.eq",
+        usage: "actual expected assert.eq",
+        description: "Fail unless two values are equal",
+    },
+    OperatorHelp {
+        name: "assert.neq",
+        usage: "actual unexpected assert.neq",
+        description: "Fail if two values are equal",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
+    match operator {
+        "assert" => {
+            let value = context.pop_operand_value()?;
+            if !context.operand_is_truthy(&value)? {
+                return Err(assertion_failed(format!(
+                    "assert: Expected a truthy value but found: {}",
+                    context.operand_display(&value)
+                )));
+            }
+            Ok(true)
+        }
+        "assert.eq" => {
+            // `actual expected assert.eq`
+            let expected = context.pop_operand_value()?;
+            let actual = context.pop_operand_value()?;
+            if let Some(difference) = find_difference(context, &actual, &expected, "") {
+                let mut message = format!(
+                    "assert.eq: Values are not equal\n    expected: {}\n    actual:   {}",
+                    context.operand_display(&expected),
+                    context.operand_display(&actual)
+                );
+                if !difference.path.is_empty() {
+                    message.push_str(&format!(
+                        "\n    first difference at {}: expected {}, actual {}",
+                        difference.path, difference.expected, difference.actual
+                    ));
+                }
+                return Err(assertion_failed(message));
+            }
+            Ok(true)
+        }
+        "assert.neq" => {
+            let unexpected = context.pop_operand_value()?;
+            let actual = context.pop_operand_value()?;
+            if find_difference(context, &actual, &unexpected, "").is_none() {
+                return Err(assertion_failed(format!(
+                    "assert.neq: Expected values to differ but both were: {}",
+                    context.operand_display(&actual)
+                )));
+            }
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+pub fn assertion_failed(message: String) -> RuntimeError {
+    RuntimeError::new(ErrorKind::AssertionFailed, message)
+}
+
+/// Where two values first differ, e.g. `[1].name` inside nested scopes.
+struct Difference {
+    path: String,
+    expected: String,
+    actual: String,
+}
+
+/// Compare two values structurally, returning the first difference if they are not equal.
+/// Scopes are equal when their operands and variables are, and blocks are never equal.
+fn find_difference(
+    context: &ExecutionContext,
+    actual: &Operand,
+    expected: &Operand,
+    path: &str,
+) -> Option<Difference> {
+    let difference = || {
+        Some(Difference {
+            path: path.to_string(),
+            expected: context.operand_display(expected),
+            actual: context.operand_display(actual),
+        })
+    };
+
+    match (actual, expected) {
+        (Operand::Variable(name), _) => match context.get_variable(name) {
+            Some(inner) => find_difference(context, inner, expected, path),
+            None => difference(),
+        },
+        (_, Operand::Variable(name)) => match context.get_variable(name) {
+            Some(inner) => find_difference(context, actual, inner, path),
+            None => difference(),
+        },
+        (Operand::Number(a), Operand::Number(b)) if a == b => None,
+        (Operand::String(a), Operand::String(b)) if a == b => None,
+        (Operand::Boolean(a), Operand::Boolean(b)) if a == b => None,
+        (Operand::Null, Operand::Null) => None,
+        (Operand::Scope(a), Operand::Scope(b)) => {
+            let a_stack = a.get_operand_stack();
+            let b_stack = b.get_operand_stack();
+            for (i, (a_item, b_item)) in a_stack.iter().zip(b_stack).enumerate() {
+                let found = find_difference(context, a_item, b_item, &format!("{}[{}]", path, i));
+                if found.is_some() {
+                    return found;
+                }
+            }
+            if a_stack.len() != b_stack.len() {
+                return Some(Difference {
+                    path: format!("{}.length", path),
+                    expected: b_stack.len().to_string(),
+                    actual: a_stack.len().to_string(),
+                });
+            }
+
+            let mut names: Vec<&String> = a
+                .get_variable_state()
+                .keys()
+                .chain(b.get_variable_state().keys())
+                .collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let item_path = format!("{}.{}", path, name);
+                match (
+                    a.get_variable_state().get(name),
+                    b.get_variable_state().get(name),
+                ) {
+                    (Some(a_item), Some(b_item)) => {
+                        let found = find_difference(context, a_item, b_item, &item_path);
+                        if found.is_some() {
+                            return found;
+                        }
+                    }
+                    (a_item, b_item) => {
+                        let display = |item: Option<&Operand>| match item {
+                            Some(item) => context.operand_display(item),
+                            None => "(missing)".to_string(),
+                        };
+                        return Some(Difference {
+                            path: item_path,
+                            expected: display(b_item),
+                            actual: display(a_item),
+                        });
+                    }
+                }
+            }
+            None
+        }
+        _ => difference(),
+    }
+}
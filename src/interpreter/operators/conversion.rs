@@ -0,0 +1,102 @@
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{Operand, OperatorHelp, error::RuntimeError},
+};
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "to.number",
+        usage: "value to.number",
+        description: "Convert to a number",
+    },
+    OperatorHelp {
+        name: "to.int",
+        usage: "value to.int",
+        description: "Convert to a number, truncating any fraction",
+    },
+    OperatorHelp {
+        name: "to.string",
+        usage: "value to.string",
+        description: "Convert to a string",
+    },
+    OperatorHelp {
+        name: "to.boolean",
+        usage: "value to.boolean",
+        description: "Whether a value is truthy",
+    },
+    OperatorHelp {
+        name: "number.format",
+        usage: "value precision width number.format",
+        description: "Format a number with fixed decimals, padded to width",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
+    match operator {
+        "to.number" => {
+            let operand = context.pop_operand_any()?;
+            let value = context.operand_to_number(&operand)?;
+            context.push_operand(Operand::Number(value));
+            Ok(true)
+        }
+        "to.int" => {
+            // Truncates towards zero e.g. `-2.7` => `-2`
+            let operand = context.pop_operand_any()?;
+            let value = context.operand_to_number(&operand)?;
+            if !value.is_finite() {
+                return Err(format!("Cannot convert to integer: {}", value).into());
+            }
+            context.push_operand(Operand::Number(value.trunc()));
+            Ok(true)
+        }
+        "to.string" => {
+            let operand = context.pop_operand_any()?;
+            let value = context.operand_to_string(&operand)?;
+            context.push_operand(Operand::String(value));
+            Ok(true)
+        }
+        "to.boolean" => {
+            // See `ExecutionContext::operand_is_truthy` for truthiness rules
+            let operand = context.pop_operand_any()?;
+            let value = context.operand_is_truthy(&operand)?;
+            context.push_operand(Operand::Boolean(value));
+            Ok(true)
+        }
+        "number.format" => {
+            // Format a number with a fixed number of decimal places, right-aligned to
+            // a minimum width e.g. `3.14159 2 8 number.format` => `"    3.14"`
+            let width = context.pop_operand_number_literal()?;
+            let precision = context.pop_operand_number_literal()?;
+            let value = context.pop_operand_number_literal()?;
+            let width = to_count(width, "width")?;
+            let precision = to_count(precision, "precision")?;
+            context.push_operand(Operand::String(format!(
+                "{:>width$.precision$}",
+                value,
+                width = width,
+                precision = precision
+            )));
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Convert a width or precision, which `format!` only supports up to `u16::MAX`.
+fn to_count(value: f64, name: &str) -> Result<usize, String> {
+    if value < 0.0 || value.fract() != 0.0 {
+        Err(format!(
+            "number.format: Expected {} to be a non-negative integer but found: {}",
+            name, value
+        ))
+    } else if value > u16::MAX as f64 {
+        Err(format!(
+            "number.format: Expected {} to be at most {} but found: {}",
+            name,
+            u16::MAX,
+            value
+        ))
+    } else {
+        Ok(value as usize)
+    }
+}
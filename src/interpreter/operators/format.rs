@@ -0,0 +1,495 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{Operand, OperatorHelp, error::RuntimeError},
+};
+
+// Templates use a subset of Rust's format syntax:
+//   `{}`       next positional argument
+//   `{1}`      positional argument by index
+//   `{name}`   variable `$name`, looked up through the scope stack
+//   `{:spec}`  spec is `[[fill]align][0][width][.precision]`, where align is one of `<`, `^`, `>`
+//              and width and precision are at most 65535
+//   `{{` `}}`  literal braces
+// e.g. `"Jeff" 93.456 "ok" "{} scored {:.1}% ({:>8})" format` => `"Jeff scored 93.5% (      ok)"`
+
+enum Segment {
+    Literal(String),
+    Placeholder { argument: Argument, spec: Spec },
+}
+
+enum Argument {
+    Next,
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    /// Pad with zeros after any sign, from a leading zero on the width
+    zero_pad: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "format",
+        usage: "args... template format",
+        description: "Fill {} placeholders in template",
+    },
+    OperatorHelp {
+        name: "format.array",
+        usage: "array template format.array",
+        description: "Fill {} placeholders in template from an array",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
+    match operator {
+        "format" => {
+            let template = context.pop_operand_string_literal()?;
+            let segments = parse_template(&template)?;
+            let argument_count = count_positional_arguments(&segments);
+
+            let available = context.current_scope_readonly().get_operand_stack().len();
+            if available < argument_count {
+                return Err(format!(
+                    "format: Template expects {} argument(s) but the stack only has {}",
+                    argument_count, available
+                )
+                .into());
+            }
+            let mut arguments = Vec::with_capacity(argument_count);
+            for _ in 0..argument_count {
+                arguments.push(context.pop_operand_any()?);
+            }
+            arguments.reverse();
+
+            let result = render(context, operator, &segments, &arguments)?;
+            context.push_operand(Operand::String(result));
+            Ok(true)
+        }
+        "format.array" => {
+            let template = context.pop_operand_string_literal()?;
+            let array = context.pop_operand_scope()?;
+            let segments = parse_template(&template)?;
+            let argument_count = count_positional_arguments(&segments);
+
+            let arguments = array.get_operand_stack();
+            if arguments.len() != argument_count {
+                return Err(format!(
+                    "format.array: Template expects {} argument(s) but the array has {}",
+                    argument_count,
+                    arguments.len()
+                )
+                .into());
+            }
+
+            let result = render(context, operator, &segments, arguments)?;
+            context.push_operand(Operand::String(result));
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn parse_template(template: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => placeholder.push(ch),
+                        None => {
+                            return Err(format!(
+                                "format: Unterminated placeholder '{{{}' in template",
+                                placeholder
+                            ));
+                        }
+                    }
+                }
+                if !literal.is_empty() {
+                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segments.push(parse_placeholder(&placeholder)?);
+            }
+            '}' => {
+                return Err(
+                    "format: Unmatched '}' in template (use '}}' for a literal brace)".to_string(),
+                );
+            }
+            _ => literal.push(ch),
+        }
+    }
+
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Segment, String> {
+    let (name, spec) = match placeholder.split_once(':') {
+        Some((name, spec)) => (name, spec),
+        None => (placeholder, ""),
+    };
+
+    let argument = if name.is_empty() {
+        Argument::Next
+    } else if let Ok(index) = name.parse::<usize>() {
+        Argument::Index(index)
+    } else if name.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+        Argument::Name(name.to_string())
+    } else {
+        return Err(format!("format: Invalid placeholder '{{{}}}'", placeholder));
+    };
+
+    Ok(Segment::Placeholder {
+        argument,
+        spec: parse_spec(spec)
+            .map_err(|err| format!("format: {} in '{{{}}}'", err, placeholder))?,
+    })
+}
+
+fn parse_spec(spec: &str) -> Result<Spec, String> {
+    let mut result = Spec::default();
+    let chars: Vec<char> = spec.chars().collect();
+    let mut i = 0;
+
+    // Alignment, optionally preceded by a fill character
+    if chars.len() >= 2 && ['<', '^', '>'].contains(&chars[1]) {
+        result.fill = Some(chars[0]);
+        result.align = Some(chars[1]);
+        i = 2;
+    } else if !chars.is_empty() && ['<', '^', '>'].contains(&chars[0]) {
+        result.align = Some(chars[0]);
+        i = 1;
+    }
+
+    // Leading zero on the width means zero-padding e.g. `{:03}` => `005`, `-05`
+    if result.align.is_none() && chars.get(i) == Some(&'0') {
+        result.zero_pad = true;
+        i += 1;
+    }
+
+    let width: String = chars[i..]
+        .iter()
+        .take_while(|ch| ch.is_ascii_digit())
+        .collect();
+    i += width.len();
+    if !width.is_empty() {
+        result.width = Some(parse_count(&width, "width")?);
+    }
+
+    if chars.get(i) == Some(&'.') {
+        i += 1;
+        let precision: String = chars[i..]
+            .iter()
+            .take_while(|ch| ch.is_ascii_digit())
+            .collect();
+        if precision.is_empty() {
+            return Err("Missing precision after '.'".to_string());
+        }
+        i += precision.len();
+        result.precision = Some(parse_count(&precision, "precision")?);
+    }
+
+    if i < chars.len() {
+        return Err(format!("Invalid format spec '{}'", spec));
+    }
+    Ok(result)
+}
+
+/// Parse a width or precision, which `format!` only supports up to `u16::MAX`.
+fn parse_count(digits: &str, name: &str) -> Result<usize, String> {
+    match digits.parse::<u16>() {
+        Ok(count) => Ok(count as usize),
+        Err(_) => Err(format!(
+            "Expected {} to be at most {} but found {}",
+            name,
+            u16::MAX,
+            digits
+        )),
+    }
+}
+
+/// Number of operands a template consumes, i.e. `{}` placeholders or the highest `{n}` index.
+fn count_positional_arguments(segments: &[Segment]) -> usize {
+    let mut next = 0;
+    let mut count = 0;
+    for segment in segments {
+        match segment {
+            Segment::Placeholder {
+                argument: Argument::Next,
+                ..
+            } => {
+                next += 1;
+                count = count.max(next);
+            }
+            Segment::Placeholder {
+                argument: Argument::Index(index),
+                ..
+            } => {
+                count = count.max(index + 1);
+            }
+            _ => {}
+        }
+    }
+    count
+}
+
+fn render(
+    context: &ExecutionContext,
+    operator: &str,
+    segments: &[Segment],
+    arguments: &[Operand],
+) -> Result<String, RuntimeError> {
+    let mut result = String::new();
+    let mut next = 0;
+
+    for segment in segments {
+        match segment {
+            Segment::Literal(text) => result.push_str(text),
+            Segment::Placeholder { argument, spec } => {
+                let operand = match argument {
+                    Argument::Next => {
+                        next += 1;
+                        &arguments[next - 1]
+                    }
+                    Argument::Index(index) => &arguments[*index],
+                    Argument::Name(name) => match context.get_variable(name) {
+                        Some(value) => value,
+                        None => {
+                            return Err(format!("format: Variable '{}' not found", name).into());
+                        }
+                    },
+                };
+                result.push_str(&render_operand(context, operator, operand, spec)?);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn render_operand(
+    context: &ExecutionContext,
+    operator: &str,
+    operand: &Operand,
+    spec: &Spec,
+) -> Result<String, RuntimeError> {
+    let operand = match operand {
+        Operand::Variable(name) => match context.get_variable(name) {
+            Some(value) => value,
+            None => return Err(format!("format: Variable '{}' not found", name).into()),
+        },
+        _ => operand,
+    };
+
+    let text = match (operand, spec.precision) {
+        (Operand::Number(value), Some(precision)) => format!("{:.*}", precision, value),
+        (_, Some(precision)) => context
+            .operand_to_string(operand)?
+            .graphemes(true)
+            .take(precision)
+            .collect(),
+        (_, None) => context.operand_to_string(operand)?,
+    };
+
+    let width = spec.width.unwrap_or(0);
+    let length = text.graphemes(true).count();
+    if length >= width {
+        return Ok(text);
+    }
+
+    // Numbers align right by default, everything else aligns left
+    let is_number = matches!(operand, Operand::Number(_));
+    let (fill, align) = match (spec.zero_pad, is_number) {
+        (true, _) => ('0', '>'),
+        (false, true) => (spec.fill.unwrap_or(' '), spec.align.unwrap_or('>')),
+        (false, false) => (spec.fill.unwrap_or(' '), spec.align.unwrap_or('<')),
+    };
+    let padding = width - length;
+    let (before, after) = match align {
+        '>' => (padding, 0),
+        '^' => (padding / 2, padding - padding / 2),
+        _ => (0, padding),
+    };
+    let mut result = context.reserve_string(
+        operator,
+        fill.len_utf8()
+            .checked_mul(padding)
+            .and_then(|bytes| bytes.checked_add(text.len())),
+    )?;
+
+    // Zeros go between the sign and the digits
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(digits) if spec.zero_pad && is_number => ("-", digits),
+        _ => ("", text.as_str()),
+    };
+    result.push_str(sign);
+    result.extend(std::iter::repeat_n(fill, before));
+    result.push_str(text);
+    result.extend(std::iter::repeat_n(fill, after));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        execution_context::capabilities::Capabilities,
+        interpreter::{error::ErrorKind, limits::Limit},
+    };
+
+    fn format_with(
+        context: &ExecutionContext,
+        template: &str,
+        arguments: &[Operand],
+    ) -> Result<String, RuntimeError> {
+        render(context, "format", &parse_template(template)?, arguments)
+    }
+
+    fn format(template: &str, arguments: &[Operand]) -> Result<String, RuntimeError> {
+        format_with(
+            &ExecutionContext::new(Capabilities::none()),
+            template,
+            arguments,
+        )
+    }
+
+    fn number(value: f64) -> Operand {
+        Operand::Number(value)
+    }
+
+    fn string(value: &str) -> Operand {
+        Operand::String(value.to_string())
+    }
+
+    fn error(template: &str, arguments: &[Operand]) -> String {
+        format(template, arguments).unwrap_err().message
+    }
+
+    #[test]
+    fn arguments() {
+        let arguments = [string("a"), number(2.0)];
+        assert_eq!(format("{} and {}", &arguments).unwrap(), "a and 2");
+        assert_eq!(format("{1} {0} {1}", &arguments).unwrap(), "2 a 2");
+        assert_eq!(format("{{{}}} }}{{", &arguments[..1]).unwrap(), "{a} }{");
+        assert_eq!(
+            count_positional_arguments(&parse_template("{} {3} {}").unwrap()),
+            4
+        );
+
+        let mut context = ExecutionContext::new(Capabilities::none());
+        context.set_variable("name".to_string(), string("Jeff"));
+        assert_eq!(format_with(&context, "hi {name}", &[]).unwrap(), "hi Jeff");
+        assert_eq!(
+            error("{missing}", &[]),
+            "format: Variable 'missing' not found"
+        );
+    }
+
+    #[test]
+    fn alignment_and_fill() {
+        // Numbers align right and everything else left, unless told otherwise
+        assert_eq!(format("[{:4}]", &[number(7.0)]).unwrap(), "[   7]");
+        assert_eq!(format("[{:4}]", &[string("ab")]).unwrap(), "[ab  ]");
+        assert_eq!(format("[{:<4}]", &[number(7.0)]).unwrap(), "[7   ]");
+        assert_eq!(format("[{:>4}]", &[string("ab")]).unwrap(), "[  ab]");
+        assert_eq!(format("[{:^5}]", &[string("ab")]).unwrap(), "[ ab  ]");
+        assert_eq!(format("[{:*^6}]", &[string("ab")]).unwrap(), "[**ab**]");
+        assert_eq!(format("[{:🐈>3}]", &[string("a")]).unwrap(), "[🐈🐈a]");
+        // Width is a minimum, measured in characters
+        assert_eq!(format("[{:2}]", &[string("abc")]).unwrap(), "[abc]");
+        assert_eq!(format("[{:3}]", &[string("🐈")]).unwrap(), "[🐈  ]");
+    }
+
+    #[test]
+    fn zero_padding_goes_after_the_sign() {
+        assert_eq!(format("{:05}", &[number(42.0)]).unwrap(), "00042");
+        assert_eq!(format("{:05}", &[number(-3.0)]).unwrap(), "-0003");
+        assert_eq!(format("{:06.2}", &[number(-1.5)]).unwrap(), "-01.50");
+        // An explicit fill is just a fill
+        assert_eq!(format("{:0>5}", &[number(-3.0)]).unwrap(), "000-3");
+        assert_eq!(format("{:03}", &[string("ab")]).unwrap(), "0ab");
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!(format("{:.2}", &[number(1.23456)]).unwrap(), "1.23");
+        assert_eq!(format("{:.0}", &[number(2.5)]).unwrap(), "2");
+        assert_eq!(format("{:8.1}", &[number(93.456)]).unwrap(), "    93.5");
+        // Strings are cut to that many characters
+        assert_eq!(format("{:.2}", &[string("🐈abc")]).unwrap(), "🐈a");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert_eq!(
+            error("{", &[]),
+            "format: Unterminated placeholder '{' in template"
+        );
+        assert_eq!(
+            error("}", &[]),
+            "format: Unmatched '}' in template (use '}}' for a literal brace)"
+        );
+        assert_eq!(error("{a-b}", &[]), "format: Invalid placeholder '{a-b}'");
+        assert_eq!(
+            error("{:x}", &[number(1.0)]),
+            "format: Invalid format spec 'x' in '{:x}'"
+        );
+        assert_eq!(
+            error("{:.}", &[number(1.0)]),
+            "format: Missing precision after '.' in '{:.}'"
+        );
+    }
+
+    #[test]
+    fn width_and_precision_are_limited() {
+        assert_eq!(
+            format("{:65535}", &[string("")]).unwrap().len(),
+            u16::MAX as usize
+        );
+        assert_eq!(
+            error("{:.70000}", &[number(1.0)]),
+            "format: Expected precision to be at most 65535 but found 70000 in '{:.70000}'"
+        );
+        assert_eq!(
+            error("{:99999999999}", &[number(1.0)]),
+            "format: Expected width to be at most 65535 but found 99999999999 in '{:99999999999}'"
+        );
+    }
+
+    #[test]
+    fn padding_counts_against_the_memory_limit() {
+        let mut context = ExecutionContext::new(Capabilities::none());
+        context.set_memory_limit(Some(1000));
+        assert_eq!(
+            format_with(&context, "{:900}", &[number(1.0)])
+                .unwrap()
+                .len(),
+            900
+        );
+        let err = format_with(&context, "{:2000}", &[number(1.0)]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::LimitExceeded(Limit::Memory)));
+    }
+}
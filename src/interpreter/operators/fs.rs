@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    io::{BufReader, Write},
+    path::Path,
+};
+
+use crate::{
+    execution_context::{ExecutionContext, scope::Scope},
+    interpreter::{Operand, OperatorHelp, error::RuntimeError, operators::read_string},
+};
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "file.read",
+        usage: "path file.read",
+        description: "Read a whole file",
+    },
+    OperatorHelp {
+        name: "file.lines",
+        usage: "path file.lines",
+        description: "Read a file as an array of lines",
+    },
+    OperatorHelp {
+        name: "file.write",
+        usage: "content path file.write",
+        description: "Write (replace) a file",
+    },
+    OperatorHelp {
+        name: "file.append",
+        usage: "content path file.append",
+        description: "Append to a file",
+    },
+    OperatorHelp {
+        name: "file.exists",
+        usage: "path file.exists",
+        description: "Whether a file or directory exists",
+    },
+    OperatorHelp {
+        name: "file.delete",
+        usage: "path file.delete",
+        description: "Delete a file",
+    },
+    OperatorHelp {
+        name: "dir.list",
+        usage: "path dir.list",
+        description: "Sorted array of the entries in a directory",
+    },
+    OperatorHelp {
+        name: "path.join",
+        usage: "a b path.join",
+        description: "Join two paths",
+    },
+    OperatorHelp {
+        name: "path.basename",
+        usage: "path path.basename",
+        description: "Last component of a path",
+    },
+    OperatorHelp {
+        name: "path.extension",
+        usage: "path path.extension",
+        description: "Extension of a path, or null",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
+    match operator {
+        "file.read" => {
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            let contents = read_file(context, operator, &path)?;
+            context.push_operand(Operand::String(contents));
+            Ok(true)
+        }
+        "file.lines" => {
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            let contents = read_file(context, operator, &path)?;
+            let lines = contents
+                .lines()
+                .map(|line| Operand::String(line.to_string()))
+                .collect();
+            context.push_operand(Operand::Scope(Scope::from_operands(lines)));
+            Ok(true)
+        }
+        "file.write" => {
+            // e.g. `"some text" "out.txt" file.write`
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            let operand = context.pop_operand_any()?;
+            let contents = context.operand_to_string(&operand)?;
+            fs::write(&path, contents).map_err(|err| io_error(operator, &path, err))?;
+            Ok(true)
+        }
+        "file.append" => {
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            let operand = context.pop_operand_any()?;
+            let contents = context.operand_to_string(&operand)?;
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(contents.as_bytes()))
+                .map_err(|err| io_error(operator, &path, err))?;
+            Ok(true)
+        }
+        "file.exists" => {
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            context.push_operand(Operand::Boolean(Path::new(&path).exists()));
+            Ok(true)
+        }
+        "file.delete" => {
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            fs::remove_file(&path).map_err(|err| io_error(operator, &path, err))?;
+            Ok(true)
+        }
+        "dir.list" => {
+            // Entry names (not full paths), sorted alphabetically
+            let path = context.pop_operand_string_literal()?;
+            context.require_path(operator, &path)?;
+            let mut names = Vec::new();
+            for entry in fs::read_dir(&path).map_err(|err| io_error(operator, &path, err))? {
+                let entry = entry.map_err(|err| io_error(operator, &path, err))?;
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+            names.sort();
+            let entries = names.into_iter().map(Operand::String).collect();
+            context.push_operand(Operand::Scope(Scope::from_operands(entries)));
+            Ok(true)
+        }
+        "path.join" => {
+            let right = context.pop_operand_string_literal()?;
+            let left = context.pop_operand_string_literal()?;
+            let joined = Path::new(&left).join(right);
+            context.push_operand(Operand::String(joined.to_string_lossy().to_string()));
+            Ok(true)
+        }
+        "path.basename" => {
+            let path = context.pop_operand_string_literal()?;
+            let basename = match Path::new(&path).file_name() {
+                Some(name) => Operand::String(name.to_string_lossy().to_string()),
+                None => Operand::Null,
+            };
+            context.push_operand(basename);
+            Ok(true)
+        }
+        "path.extension" => {
+            // e.g. `"notes.txt"` => `"txt"`, or `null` if there is no extension
+            let path = context.pop_operand_string_literal()?;
+            let extension = match Path::new(&path).extension() {
+                Some(extension) => Operand::String(extension.to_string_lossy().to_string()),
+                None => Operand::Null,
+            };
+            context.push_operand(extension);
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Read a whole file, within the memory limit.
+fn read_file(
+    context: &ExecutionContext,
+    operator: &str,
+    path: &str,
+) -> Result<String, RuntimeError> {
+    let file = fs::File::open(path).map_err(|err| io_error(operator, path, err))?;
+    read_string(context, operator, BufReader::new(file), |err| {
+        io_error(operator, path, err)
+    })
+}
+
+fn io_error(operator: &str, path: &str, err: std::io::Error) -> String {
+    format!("{}: Cannot access '{}': {}", operator, path, err)
+}
@@ -1,13 +1,42 @@
-use crate::execution_context::ExecutionContext;
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{Operand, OperatorHelp, error::RuntimeError},
+};
 
-pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, String> {
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "set",
+        usage: "value $name set",
+        description: "Set a variable",
+    },
+    OperatorHelp {
+        name: "throw",
+        usage: "value throw",
+        description: "Raise an error carrying any value",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
     match operator {
         "set" => {
             let variable_name = context.pop_operand_variable_identifier()?;
             let value = context.pop_operand_any()?;
+            if let Operand::Variable(name) = value {
+                return Err(format!(
+                    "set: Cannot set '{}' to the variable identifier ${}",
+                    variable_name, name
+                )
+                .into());
+            }
             context.set_variable(variable_name, value);
             Ok(true)
         }
+        "throw" => {
+            // Any value can be thrown, and is handed to the handler of the enclosing `try`
+            let value = context.pop_operand_value()?;
+            let message = context.operand_to_string(&value)?;
+            Err(RuntimeError::thrown(value, message))
+        }
         _ => Ok(false),
     }
 }
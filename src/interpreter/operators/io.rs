@@ -1,26 +1,143 @@
-use crate::execution_context::ExecutionContext;
+use std::io;
 
-pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, String> {
+use crate::{
+    execution_context::{ExecutionContext, capabilities::Capability},
+    interpreter::{Operand, OperatorHelp, error::RuntimeError, operators::read_string},
+};
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "print",
+        usage: "value print",
+        description: "Print a value followed by a newline",
+    },
+    OperatorHelp {
+        name: "print.no_newline",
+        usage: "value print.no_newline",
+        description: "Print a value without a newline",
+    },
+    OperatorHelp {
+        name: "write",
+        usage: "value write",
+        description: "Same as print.no_newline",
+    },
+    OperatorHelp {
+        name: "eprint",
+        usage: "value eprint",
+        description: "Print a value to stderr",
+    },
+    OperatorHelp {
+        name: "print.stack",
+        usage: "print.stack",
+        description: "Print every operand on the stack",
+    },
+    OperatorHelp {
+        name: "read.line",
+        usage: "read.line",
+        description: "Read a line from stdin, or null at the end of input",
+    },
+    OperatorHelp {
+        name: "read.all",
+        usage: "read.all",
+        description: "Read all of stdin",
+    },
+    OperatorHelp {
+        name: "read.number",
+        usage: "read.number",
+        description: "Read a line from stdin as a number, or null at the end of input",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
     match operator {
         "print" => {
+            context.require_capability(operator, Capability::Stdout)?;
             let operand = context.pop_operand_any()?;
             let output = context.operand_to_string(&operand)?;
-            println!("{output}");
+            writeln!(context.output.stdout(), "{output}").map_err(stdout_error)?;
+            Ok(true)
+        }
+        "print.no_newline" | "write" => {
+            context.require_capability(operator, Capability::Stdout)?;
+            let operand = context.pop_operand_any()?;
+            let output = context.operand_to_string(&operand)?;
+            let mut stdout = context.output.stdout();
+            write!(stdout, "{output}").map_err(stdout_error)?;
+            // Flush so that e.g. prompts appear before reading input
+            stdout.flush().map_err(stdout_error)?;
+            Ok(true)
+        }
+        "eprint" => {
+            context.require_capability(operator, Capability::Stdout)?;
+            let operand = context.pop_operand_any()?;
+            let output = context.operand_to_string(&operand)?;
+            writeln!(context.output.stderr(), "{output}")
+                .map_err(|err| format!("Error writing to stderr: {}", err))?;
             Ok(true)
         }
         "print.stack" => {
-            print!("Stack [");
-            let scope = context.current_scope_readonly();
-            let stack = scope.get_operand_stack();
-            for (i, operand) in stack.iter().enumerate() {
-                if i > 0 {
-                    print!(", ");
-                }
-                print!("{}", context.operand_display(operand));
-            }
-            println!("]");
+            context.require_capability(operator, Capability::Stdout)?;
+            let operands: Vec<String> = context
+                .current_scope_readonly()
+                .get_operand_stack()
+                .iter()
+                .map(|operand| context.operand_display(operand))
+                .collect();
+            writeln!(context.output.stdout(), "Stack [{}]", operands.join(", "))
+                .map_err(stdout_error)?;
+            Ok(true)
+        }
+        "read.line" => {
+            // Pushes `null` once there is no more input
+            context.require_capability(operator, Capability::Stdin)?;
+            let operand = match read_line()? {
+                Some(line) => Operand::String(line),
+                None => Operand::Null,
+            };
+            context.push_operand(operand);
+            Ok(true)
+        }
+        "read.all" => {
+            context.require_capability(operator, Capability::Stdin)?;
+            let input = read_string(context, operator, io::stdin().lock(), stdin_error)?;
+            context.push_operand(Operand::String(input));
+            Ok(true)
+        }
+        "read.number" => {
+            // Pushes `null` once there is no more input
+            context.require_capability(operator, Capability::Stdin)?;
+            let operand = match read_line()? {
+                Some(line) => Operand::Number(context.operand_to_number(&Operand::String(line))?),
+                None => Operand::Null,
+            };
+            context.push_operand(operand);
             Ok(true)
         }
         _ => Ok(false),
     }
 }
+
+fn stdout_error(err: io::Error) -> String {
+    format!("Error writing to stdout: {}", err)
+}
+
+fn stdin_error(err: io::Error) -> String {
+    format!("Error reading from stdin: {}", err)
+}
+
+/// Read the next line from stdin without its line ending, or `None` at EOF.
+fn read_line() -> Result<Option<String>, String> {
+    let mut line = String::new();
+    let bytes_read = io::stdin().read_line(&mut line).map_err(stdin_error)?;
+    if bytes_read == 0 {
+        return Ok(None);
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
@@ -1,6 +1,32 @@
-use crate::{execution_context::ExecutionContext, interpreter::Operand};
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{Operand, OperatorHelp, error::RuntimeError},
+};
 
-pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, String> {
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "+",
+        usage: "a b +",
+        description: "Add two numbers",
+    },
+    OperatorHelp {
+        name: "-",
+        usage: "a b -",
+        description: "Subtract b from a",
+    },
+    OperatorHelp {
+        name: "*",
+        usage: "a b *",
+        description: "Multiply two numbers",
+    },
+    OperatorHelp {
+        name: "/",
+        usage: "a b /",
+        description: "Divide a by b",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
     match operator {
         "+" => {
             let right = context.pop_operand_number_literal()?;
@@ -24,7 +50,7 @@ pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, S
             let right = context.pop_operand_number_literal()?;
             let left = context.pop_operand_number_literal()?;
             match right {
-                0.0 => Err(format!("Division by zero")),
+                0.0 => Err("Division by zero".to_string().into()),
                 _ => {
                     context.push_operand(Operand::Number(left / right));
                     Ok(true)
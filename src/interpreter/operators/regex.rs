@@ -0,0 +1,143 @@
+use crate::{
+    execution_context::{ExecutionContext, scope::Scope},
+    interpreter::{Operand, OperatorHelp, error::RuntimeError},
+};
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "regex.match",
+        usage: "str pattern regex.match",
+        description: "Whether the pattern matches anywhere in str",
+    },
+    OperatorHelp {
+        name: "regex.find",
+        usage: "str pattern regex.find",
+        description: "First match, or null",
+    },
+    OperatorHelp {
+        name: "regex.find_all",
+        usage: "str pattern regex.find_all",
+        description: "Array of every match",
+    },
+    OperatorHelp {
+        name: "regex.captures",
+        usage: "str pattern regex.captures",
+        description: "Capture groups of the first match, or null",
+    },
+    OperatorHelp {
+        name: "regex.replace",
+        usage: "str pattern replacement regex.replace",
+        description: "Replace the first match",
+    },
+    OperatorHelp {
+        name: "regex.replace_all",
+        usage: "str pattern replacement regex.replace_all",
+        description: "Replace every match",
+    },
+    OperatorHelp {
+        name: "regex.split",
+        usage: "str pattern regex.split",
+        description: "Split into an array around matches",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
+    match operator {
+        "regex.match" => {
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let regex = context.get_regex(&pattern)?;
+            context.push_operand(Operand::Boolean(regex.is_match(&value)));
+            Ok(true)
+        }
+        "regex.find" => {
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let regex = context.get_regex(&pattern)?;
+            let result = match regex.find(&value) {
+                Some(m) => Operand::String(m.as_str().to_string()),
+                None => Operand::Null,
+            };
+            context.push_operand(result);
+            Ok(true)
+        }
+        "regex.find_all" => {
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let regex = context.get_regex(&pattern)?;
+            let matches = regex
+                .find_iter(&value)
+                .map(|m| Operand::String(m.as_str().to_string()))
+                .collect();
+            context.push_operand(Operand::Scope(Scope::from_operands(matches)));
+            Ok(true)
+        }
+        "regex.captures" => {
+            // Capture groups of the first match. Every group is pushed as an array element
+            // (group 0 being the whole match), and named groups are also set as variables
+            // e.g. `"2024-06" "(?<year>\d+)-(?<month>\d+)" regex.captures` => { "2024-06", "2024", "06", year = "2024", month = "06" }
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let regex = context.get_regex(&pattern)?;
+            let result = match regex.captures(&value) {
+                Some(captures) => {
+                    let groups = captures
+                        .iter()
+                        .map(|group| match group {
+                            Some(m) => Operand::String(m.as_str().to_string()),
+                            None => Operand::Null,
+                        })
+                        .collect();
+                    let mut scope = Scope::from_operands(groups);
+                    for name in regex.capture_names().flatten() {
+                        let value = match captures.name(name) {
+                            Some(m) => Operand::String(m.as_str().to_string()),
+                            None => Operand::Null,
+                        };
+                        scope.set_variable(name.to_string(), value);
+                    }
+                    Operand::Scope(scope)
+                }
+                None => Operand::Null,
+            };
+            context.push_operand(result);
+            Ok(true)
+        }
+        "regex.replace" | "regex.replace_all" => {
+            // Replacement may reference capture groups e.g. `${1}` or `${name}`
+            let replacement = context.pop_operand_string_literal()?;
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let regex = context.get_regex(&pattern)?;
+            // An empty pattern matches between every character, so this can grow a lot.
+            // Capture group references are not expanded, so this is only an estimate.
+            let matches = match operator {
+                "regex.replace" => 1,
+                _ => regex.find_iter(&value).count(),
+            };
+            context.reserve_memory(
+                operator,
+                value.len() + matches.saturating_mul(replacement.len()),
+            )?;
+            let result = if operator == "regex.replace" {
+                regex.replace(&value, replacement.as_str())
+            } else {
+                regex.replace_all(&value, replacement.as_str())
+            };
+            context.push_operand(Operand::String(result.into_owned()));
+            Ok(true)
+        }
+        "regex.split" => {
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let regex = context.get_regex(&pattern)?;
+            let parts = regex
+                .split(&value)
+                .map(|part| Operand::String(part.to_string()))
+                .collect();
+            context.push_operand(Operand::Scope(Scope::from_operands(parts)));
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
@@ -1,13 +1,296 @@
-use crate::{execution_context::ExecutionContext, interpreter::Operand};
+use std::mem;
 
-pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, String> {
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    execution_context::{ExecutionContext, scope::Scope},
+    interpreter::{Operand, OperatorHelp, error::RuntimeError},
+};
+
+// @NOTE All lengths and indices are measured in graphemes (user-perceived characters)
+// rather than bytes, so that e.g. "🐈" has a length of 1
+
+pub const OPERATORS: &[OperatorHelp] = &[
+    OperatorHelp {
+        name: "string.concat",
+        usage: "a b string.concat",
+        description: "Join two strings",
+    },
+    OperatorHelp {
+        name: "string.length",
+        usage: "str string.length",
+        description: "Number of characters in a string",
+    },
+    OperatorHelp {
+        name: "string.upper",
+        usage: "str string.upper",
+        description: "Convert to upper case",
+    },
+    OperatorHelp {
+        name: "string.lower",
+        usage: "str string.lower",
+        description: "Convert to lower case",
+    },
+    OperatorHelp {
+        name: "string.trim",
+        usage: "str string.trim",
+        description: "Remove whitespace from both ends",
+    },
+    OperatorHelp {
+        name: "string.split",
+        usage: "str separator string.split",
+        description: "Split into an array",
+    },
+    OperatorHelp {
+        name: "string.join",
+        usage: "array separator string.join",
+        description: "Join an array into a string",
+    },
+    OperatorHelp {
+        name: "string.substring",
+        usage: "str start end string.substring",
+        description: "Characters from start up to (not including) end",
+    },
+    OperatorHelp {
+        name: "string.replace",
+        usage: "str pattern replacement string.replace",
+        description: "Replace every occurrence of pattern",
+    },
+    OperatorHelp {
+        name: "string.starts_with",
+        usage: "str prefix string.starts_with",
+        description: "Whether str starts with prefix",
+    },
+    OperatorHelp {
+        name: "string.ends_with",
+        usage: "str suffix string.ends_with",
+        description: "Whether str ends with suffix",
+    },
+    OperatorHelp {
+        name: "string.contains",
+        usage: "str needle string.contains",
+        description: "Whether str contains needle",
+    },
+    OperatorHelp {
+        name: "string.repeat",
+        usage: "str count string.repeat",
+        description: "Repeat a string count times",
+    },
+    OperatorHelp {
+        name: "string.pad_left",
+        usage: "str width padding string.pad_left",
+        description: "Pad the start of a string to width characters",
+    },
+    OperatorHelp {
+        name: "string.pad_right",
+        usage: "str width padding string.pad_right",
+        description: "Pad the end of a string to width characters",
+    },
+    OperatorHelp {
+        name: "string.chars",
+        usage: "str string.chars",
+        description: "Split into an array of characters",
+    },
+    OperatorHelp {
+        name: "string.reverse",
+        usage: "str string.reverse",
+        description: "Reverse the characters of a string",
+    },
+];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
     match operator {
         "string.concat" => {
             let right = context.pop_operand_string_literal()?;
             let left = context.pop_operand_string_literal()?;
+            context.reserve_memory(operator, left.len() + right.len())?;
             context.push_operand(Operand::String(format!("{}{}", left, right)));
             Ok(true)
         }
+        "string.length" => {
+            let value = context.pop_operand_string_literal()?;
+            let length = value.graphemes(true).count();
+            context.push_operand(Operand::Number(length as f64));
+            Ok(true)
+        }
+        "string.upper" => {
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::String(value.to_uppercase()));
+            Ok(true)
+        }
+        "string.lower" => {
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::String(value.to_lowercase()));
+            Ok(true)
+        }
+        "string.trim" => {
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::String(value.trim().to_string()));
+            Ok(true)
+        }
+        "string.split" => {
+            let separator = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let count = if separator.is_empty() {
+                value.graphemes(true).count()
+            } else {
+                value.matches(separator.as_str()).count() + 1
+            };
+            context.reserve_memory(operator, array_bytes(count) + value.len())?;
+            let parts: Vec<Operand> = if separator.is_empty() {
+                // Splitting on nothing splits into individual characters
+                value
+                    .graphemes(true)
+                    .map(|part| Operand::String(part.to_string()))
+                    .collect()
+            } else {
+                value
+                    .split(separator.as_str())
+                    .map(|part| Operand::String(part.to_string()))
+                    .collect()
+            };
+            context.push_operand(Operand::Scope(Scope::from_operands(parts)));
+            Ok(true)
+        }
+        "string.join" => {
+            let separator = context.pop_operand_string_literal()?;
+            let array = context.pop_operand_scope()?;
+            let mut parts = Vec::new();
+            for operand in array.get_operand_stack() {
+                parts.push(context.operand_to_string(operand)?);
+            }
+            let length = parts.iter().map(String::len).sum::<usize>()
+                + separator.len() * parts.len().saturating_sub(1);
+            context.reserve_memory(operator, length)?;
+            context.push_operand(Operand::String(parts.join(&separator)));
+            Ok(true)
+        }
+        "string.substring" => {
+            let end = context.pop_operand_number_literal()?;
+            let start = context.pop_operand_number_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let graphemes: Vec<&str> = value.graphemes(true).collect();
+            let start = to_index(start, "string.substring")?;
+            let end = to_index(end, "string.substring")?;
+            if start > end || end > graphemes.len() {
+                return Err(format!(
+                    "string.substring: Range {}..{} is out of bounds for string of length {}",
+                    start,
+                    end,
+                    graphemes.len()
+                )
+                .into());
+            }
+            context.push_operand(Operand::String(graphemes[start..end].concat()));
+            Ok(true)
+        }
+        "string.replace" => {
+            let replacement = context.pop_operand_string_literal()?;
+            let pattern = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            if pattern.is_empty() {
+                return Err("string.replace: Pattern cannot be empty".to_string().into());
+            }
+            let matches = value.matches(&pattern).count();
+            context.reserve_memory(
+                operator,
+                value.len() + matches.saturating_mul(replacement.len()),
+            )?;
+            context.push_operand(Operand::String(value.replace(&pattern, &replacement)));
+            Ok(true)
+        }
+        "string.starts_with" => {
+            let prefix = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::Boolean(value.starts_with(&prefix)));
+            Ok(true)
+        }
+        "string.ends_with" => {
+            let suffix = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::Boolean(value.ends_with(&suffix)));
+            Ok(true)
+        }
+        "string.contains" => {
+            let needle = context.pop_operand_string_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::Boolean(value.contains(&needle)));
+            Ok(true)
+        }
+        "string.repeat" => {
+            let count = context.pop_operand_number_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let count = to_index(count, "string.repeat")?;
+            let mut result = context.reserve_string(operator, value.len().checked_mul(count))?;
+            for _ in 0..count {
+                result.push_str(&value);
+            }
+            context.push_operand(Operand::String(result));
+            Ok(true)
+        }
+        "string.pad_left" | "string.pad_right" => {
+            let padding = context.pop_operand_string_literal()?;
+            let width = context.pop_operand_number_literal()?;
+            let value = context.pop_operand_string_literal()?;
+            let width = to_index(width, operator)?;
+            if padding.graphemes(true).count() != 1 {
+                return Err(format!(
+                    "{}: Padding must be a single character but found: \"{}\"",
+                    operator, padding
+                )
+                .into());
+            }
+            let missing = width.saturating_sub(value.graphemes(true).count());
+            let length = padding
+                .len()
+                .checked_mul(missing)
+                .and_then(|fill| fill.checked_add(value.len()));
+            let mut result = context.reserve_string(operator, length)?;
+            if operator == "string.pad_right" {
+                result.push_str(&value);
+            }
+            for _ in 0..missing {
+                result.push_str(&padding);
+            }
+            if operator == "string.pad_left" {
+                result.push_str(&value);
+            }
+            context.push_operand(Operand::String(result));
+            Ok(true)
+        }
+        "string.chars" => {
+            let value = context.pop_operand_string_literal()?;
+            let count = value.graphemes(true).count();
+            context.reserve_memory(operator, array_bytes(count) + value.len())?;
+            let chars = value
+                .graphemes(true)
+                .map(|ch| Operand::String(ch.to_string()))
+                .collect();
+            context.push_operand(Operand::Scope(Scope::from_operands(chars)));
+            Ok(true)
+        }
+        "string.reverse" => {
+            let value = context.pop_operand_string_literal()?;
+            context.push_operand(Operand::String(value.graphemes(true).rev().collect()));
+            Ok(true)
+        }
         _ => Ok(false),
     }
 }
+
+/// Memory taken by the elements of an array of `length` operands, as counted for limits.
+fn array_bytes(length: usize) -> usize {
+    length.saturating_mul(mem::size_of::<Operand>())
+}
+
+/// Convert a number operand into an index / count, failing if it is not a non-negative integer.
+fn to_index(value: f64, operator: &str) -> Result<usize, String> {
+    if value < 0.0 || value.fract() != 0.0 {
+        Err(format!(
+            "{}: Expected a non-negative integer but found: {}",
+            operator, value
+        ))
+    } else {
+        Ok(value as usize)
+    }
+}
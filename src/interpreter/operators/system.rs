@@ -0,0 +1,35 @@
+use crate::{
+    execution_context::ExecutionContext,
+    interpreter::{
+        OperatorHelp,
+        error::{ErrorKind, RuntimeError},
+    },
+};
+
+pub const OPERATORS: &[OperatorHelp] = &[OperatorHelp {
+    name: "exit",
+    usage: "code exit",
+    description: "Stop the program, exiting with code",
+}];
+
+pub fn execute(context: &mut ExecutionContext, operator: &str) -> Result<bool, RuntimeError> {
+    match operator {
+        "exit" => {
+            // Stops the program like an error that cannot be caught, and leaves exiting the
+            // process (or not, when embedded) to the host
+            let code = context.pop_operand_number_literal()?;
+            if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+                return Err(format!(
+                    "exit: Code must be a whole number from 0 to 255, not {}",
+                    code
+                )
+                .into());
+            }
+            Err(RuntimeError::new(
+                ErrorKind::Exit(code as i32),
+                format!("exit: Exited with code {}", code),
+            ))
+        }
+        _ => Ok(false),
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{interpreter::error::RuntimeError, lexer::SourceToken};
+
+/// Everything that happened while executing one token, passed to the tracer set with
+/// `Interpreter::set_tracer`. Operands are shown as `ExecutionContext::operand_display`
+/// showed them at the time, as e.g. the value of a variable on the stack can change.
+pub struct TraceEvent<'a> {
+    pub token: &'a SourceToken,
+    /// Number of blocks being executed when the token started, see `Interpreter::call_depth`
+    pub call_depth: usize,
+    /// Operand stack of the current scope before and after the token, which are different
+    /// scopes if the token opened or closed one
+    pub stack_before: &'a [String],
+    pub stack_after: &'a [String],
+    /// Names and values of the variables assigned by the token, in order
+    pub variable_writes: &'a [(String, String)],
+    /// Error raised by the token, before any `try` handles it
+    pub error: Option<&'a RuntimeError>,
+}
+
+pub type Tracer = Box<dyn FnMut(&TraceEvent) + Send>;
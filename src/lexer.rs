@@ -1,3 +1,5 @@
+use std::{fmt, path::Path, sync::Arc};
+
 use crate::lexer::char_scanner::CharScanner;
 
 mod char_scanner;
@@ -12,6 +14,7 @@ enum LexerState {
     // Comment,
     NumberLiteral,
     StringLiteral,
+    StringLiteralEscape,
     // BooleanLiteral,
     // NullLiteral,
     Operator,
@@ -19,9 +22,11 @@ enum LexerState {
     // Word,
     ScopeStart,
     ScopeEnd,
+    BlockStart,
+    BlockEnd,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     NumberLiteral(f64),
     StringLiteral(String),
@@ -29,9 +34,82 @@ pub enum Token {
     VariableIdentifier(String),
     ScopeStart,
     ScopeEnd,
+    BlockStart,
+    BlockEnd,
+}
+
+/// Line and column (both 1-based) where a token starts in the source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Range of source code covered by a sequence of tokens, from the start of the first token
+/// to the start of the last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    /// Span of a list of tokens e.g. the body of a block, or `None` if it is empty.
+    pub fn of(tokens: &[SourceToken]) -> Option<Self> {
+        Some(Self {
+            start: tokens.first()?.position,
+            end: tokens.last()?.position,
+        })
+    }
+}
+
+/// A token along with where it came from, for error messages.
+#[derive(Debug, Clone)]
+pub struct SourceToken {
+    pub token: Token,
+    pub position: Position,
+    /// Where the last character of the token is e.g. the closing `"` of a string
+    pub end: Position,
+    /// File the token was read from, if it came from a file rather than e.g. the REPL
+    pub file: Option<Arc<Path>>,
+}
+
+/// Written the way the token appears in source code, so that lexing the result gives back
+/// the same token.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::NumberLiteral(value) => write!(f, "{}", value),
+            Token::StringLiteral(value) => {
+                // Any character can be escaped, but only these have to be
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{}\"", escaped)
+            }
+            Token::Operator(op) => write!(f, "{}", op),
+            Token::VariableIdentifier(name) => write!(f, "${}", name),
+            Token::ScopeStart => write!(f, "{{"),
+            Token::ScopeEnd => write!(f, "}}"),
+            Token::BlockStart => write!(f, "["),
+            Token::BlockEnd => write!(f, "]"),
+        }
+    }
 }
 
 impl Token {
+    /// Name of the kind of token e.g. for tools reading the output of `gnarly lex`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Token::NumberLiteral(_) => "number",
+            Token::StringLiteral(_) => "string",
+            Token::Operator(_) => "operator",
+            Token::VariableIdentifier(_) => "variable",
+            Token::ScopeStart => "scope_start",
+            Token::ScopeEnd => "scope_end",
+            Token::BlockStart => "block_start",
+            Token::BlockEnd => "block_end",
+        }
+    }
+
     pub fn new_number_literal(raw_value: &str) -> Result<Self, String> {
         let value = raw_value
             .parse::<f64>()
@@ -43,16 +121,14 @@ impl Token {
     }
     pub fn new_operator(raw_value: &str) -> Result<Self, String> {
         if raw_value.is_empty() {
-            Err(format!("Cannot create operator token from empty string"))
+            Err("Cannot create operator token from empty string".to_string())
         } else {
             Ok(Token::Operator(raw_value.to_string()))
         }
     }
     pub fn new_variable_identifier(raw_value: &str) -> Result<Self, String> {
         if raw_value.is_empty() {
-            Err(format!(
-                "Cannot create variable identifier token from empty string"
-            ))
+            Err("Cannot create variable identifier token from empty string".to_string())
         } else {
             Ok(Token::VariableIdentifier(raw_value.to_string()))
         }
@@ -63,13 +139,41 @@ impl Token {
     pub fn new_scope_end() -> Result<Self, String> {
         Ok(Token::ScopeEnd)
     }
+    pub fn new_block_start() -> Result<Self, String> {
+        Ok(Token::BlockStart)
+    }
+    pub fn new_block_end() -> Result<Self, String> {
+        Ok(Token::BlockEnd)
+    }
 }
 
 pub struct Lexer {
     scanner: CharScanner,
     state: LexerState,
-    pub token_list: Vec<Token>,
+    pub token_list: Vec<SourceToken>,
     current_token_bytes: String,
+    current_token_position: Position,
+    /// Position of the character before the one being evaluated, where tokens that are ended
+    /// by the character after them e.g. numbers end
+    previous_position: Position,
+}
+
+/// Source code that could not be split into tokens.
+#[derive(Debug, Clone)]
+pub struct LexerError {
+    pub message: String,
+    /// Character the error was found at
+    pub position: Position,
+}
+
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.position.line, self.position.column
+        )
+    }
 }
 
 enum EvaluateCharResult {
@@ -82,21 +186,57 @@ enum EndTokenResult {
 }
 
 impl Lexer {
-    pub fn scan(source_code: &str) -> Result<Lexer, String> {
+    pub fn scan(source_code: &str) -> Result<Lexer, LexerError> {
+        let lexer = Self::scan_incomplete(source_code)?;
+        if let LexerState::StringLiteral | LexerState::StringLiteralEscape = lexer.state {
+            return Err(LexerError {
+                message: "Unterminated string, expected '\"'".to_string(),
+                position: lexer.current_token_position,
+            });
+        }
+        if let Some(source_token) = lexer.unclosed_bracket() {
+            let expected = match source_token.token {
+                Token::ScopeStart => '}',
+                _ => ']',
+            };
+            return Err(LexerError {
+                message: format!("Unclosed '{}', expected '{}'", source_token.token, expected),
+                position: source_token.position,
+            });
+        }
+        Ok(lexer)
+    }
+
+    /// Like `scan`, but the source code may stop part way through a string, scope or block
+    /// e.g. while it is still being typed.
+    pub fn scan_incomplete(source_code: &str) -> Result<Lexer, LexerError> {
         // Create lexer (but do not return reference)
         let mut lexer = Self {
             scanner: CharScanner::new(source_code),
             state: LexerState::Default,
             token_list: Vec::new(),
             current_token_bytes: String::new(),
+            current_token_position: Position::default(),
+            previous_position: Position::default(),
         };
 
+        // Skip a `#!` line, so that scripts can be made executable
+        if source_code.starts_with("#!") {
+            while lexer.scanner.peek().is_some_and(|ch| ch != '\n') {
+                lexer.scanner.next();
+            }
+        }
+
         // Scan source code one character at a time
         // Fail if there is any error
         while let Some(ch) = lexer.scanner.next() {
-            if let EvaluateCharResult::Invalid(err) = lexer.evaluate_char(ch) {
-                return Err(err);
+            if let EvaluateCharResult::Invalid(message) = lexer.evaluate_char(ch) {
+                return Err(LexerError {
+                    message,
+                    position: lexer.scanner.current_position(),
+                });
             }
+            lexer.previous_position = lexer.scanner.current_position();
         }
 
         // Finalize any remaining token
@@ -105,6 +245,36 @@ impl Lexer {
         Ok(lexer)
     }
 
+    /// The innermost `{` or `[` that is still open at the end of the source code. Closing
+    /// brackets without a match are left for the interpreter to report.
+    fn unclosed_bracket(&self) -> Option<&SourceToken> {
+        let mut open = Vec::new();
+        for source_token in &self.token_list {
+            match (
+                &source_token.token,
+                open.last().map(|open: &&SourceToken| &open.token),
+            ) {
+                (Token::ScopeStart | Token::BlockStart, _) => open.push(source_token),
+                (Token::ScopeEnd, Some(Token::ScopeStart))
+                | (Token::BlockEnd, Some(Token::BlockStart)) => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+        open.pop()
+    }
+
+    /// Scan the contents of a file, recording the file on each token.
+    pub fn scan_file(source_code: &str, file: &Path) -> Result<Lexer, LexerError> {
+        let mut lexer = Self::scan(source_code)?;
+        let file: Arc<Path> = Arc::from(file);
+        for source_token in lexer.token_list.iter_mut() {
+            source_token.file = Some(file.clone());
+        }
+        Ok(lexer)
+    }
+
     fn reevaluate_char_in_new_state(&mut self, state: LexerState, ch: char) -> EvaluateCharResult {
         self.state = state;
         self.evaluate_char(ch)
@@ -113,11 +283,14 @@ impl Lexer {
     fn evaluate_char(&mut self, ch: char) -> EvaluateCharResult {
         match self.state {
             LexerState::Default => {
+                // Any token started from here begins at this character
+                self.current_token_position = self.scanner.current_position();
+
                 if ch.is_whitespace() {
                     /* Whitespace */
                     EvaluateCharResult::Valid
-                } else if ch.is_digit(10)
-                    || (ch == '-' && self.scanner.peek().map_or(false, |c| c.is_digit(10)))
+                } else if ch.is_ascii_digit()
+                    || (ch == '-' && self.scanner.peek().is_some_and(|c| c.is_ascii_digit()))
                 {
                     /* NumberLiteral */
                     self.current_token_bytes = ch.to_string();
@@ -125,7 +298,7 @@ impl Lexer {
                     EvaluateCharResult::Valid
                 } else if ch == '"' {
                     /* StringLiteral */
-                    self.current_token_bytes = format!(""); // @NOTE " symbol is stripped
+                    self.current_token_bytes = String::new(); // @NOTE " symbol is stripped
                     self.state = LexerState::StringLiteral;
                     EvaluateCharResult::Valid
                 } else if SPECIAL_OPERATOR_CHARS.contains(&ch) {
@@ -142,7 +315,7 @@ impl Lexer {
                     EvaluateCharResult::Valid
                 } else if ch == '$' {
                     /* VariableIdentifier */
-                    self.current_token_bytes = format!(""); // @NOTE $ symbol is stripped
+                    self.current_token_bytes = String::new(); // @NOTE $ symbol is stripped
                     self.state = LexerState::VariableIdentifier;
                     EvaluateCharResult::Valid
                 } else if ch == '{' {
@@ -151,13 +324,19 @@ impl Lexer {
                 } else if ch == '}' {
                     /* ScopeEnd */
                     self.reevaluate_char_in_new_state(LexerState::ScopeEnd, ch)
+                } else if ch == '[' {
+                    /* BlockStart */
+                    self.reevaluate_char_in_new_state(LexerState::BlockStart, ch)
+                } else if ch == ']' {
+                    /* BlockEnd */
+                    self.reevaluate_char_in_new_state(LexerState::BlockEnd, ch)
                 } else {
                     /* Unhandled */
                     EvaluateCharResult::Invalid(format!("Unexpected character: '{}'", ch))
                 }
             }
             LexerState::NumberLiteral => {
-                if ch.is_digit(10) || ch == '.' {
+                if ch.is_ascii_digit() || ch == '.' {
                     // Continue building number literal
                     self.current_token_bytes.push(ch);
                     EvaluateCharResult::Valid
@@ -175,7 +354,7 @@ impl Lexer {
             LexerState::StringLiteral => {
                 // String is terminated by non-escaped quotemark
                 // Strings can even span over multiple lines
-                if ch == '"' && self.scanner.peek_offset(-1) != Some('\\') {
+                if ch == '"' {
                     // Terminate string
                     match self.end_token() {
                         EndTokenResult::Valid => {
@@ -187,6 +366,7 @@ impl Lexer {
                 } else if ch == '\\' {
                     // Skip escape character
                     // Combination of escape + char will be handled by next char
+                    self.state = LexerState::StringLiteralEscape;
                     EvaluateCharResult::Valid
                 } else {
                     // Continue string literal
@@ -194,6 +374,16 @@ impl Lexer {
                     EvaluateCharResult::Valid
                 }
             }
+            LexerState::StringLiteralEscape => {
+                // Only quote marks and backslashes are escaped e.g. `\"` => `"`, `\\` => `\`
+                // Any other backslash is kept, so regex patterns like `"\d+"` can be written as is
+                if ch != '"' && ch != '\\' {
+                    self.current_token_bytes.push('\\');
+                }
+                self.current_token_bytes.push(ch);
+                self.state = LexerState::StringLiteral;
+                EvaluateCharResult::Valid
+            }
             LexerState::Operator => {
                 // Check if we're building a special symbol operator or word-based operator
                 if self.current_token_bytes.len() == 1
@@ -207,7 +397,7 @@ impl Lexer {
                         }
                         EndTokenResult::Invalid(err) => EvaluateCharResult::Invalid(err),
                     }
-                } else if ch.is_alphanumeric() || ch == '.' {
+                } else if ch.is_alphanumeric() || ch == '.' || ch == '_' {
                     // Continue building word-based operator
                     self.current_token_bytes.push(ch);
                     EvaluateCharResult::Valid
@@ -259,6 +449,28 @@ impl Lexer {
                     EndTokenResult::Invalid(err) => EvaluateCharResult::Invalid(err),
                 }
             }
+            LexerState::BlockStart => {
+                // Block start is always just one character
+                self.current_token_bytes = format!("{}", ch);
+                match self.end_token() {
+                    EndTokenResult::Valid => {
+                        self.state = LexerState::Default;
+                        EvaluateCharResult::Valid
+                    }
+                    EndTokenResult::Invalid(err) => EvaluateCharResult::Invalid(err),
+                }
+            }
+            LexerState::BlockEnd => {
+                // Block end is always just one character
+                self.current_token_bytes = format!("{}", ch);
+                match self.end_token() {
+                    EndTokenResult::Valid => {
+                        self.state = LexerState::Default;
+                        EvaluateCharResult::Valid
+                    }
+                    EndTokenResult::Invalid(err) => EvaluateCharResult::Invalid(err),
+                }
+            }
         }
     }
 
@@ -271,7 +483,7 @@ impl Lexer {
             LexerState::NumberLiteral => {
                 self.process_new_token(Token::new_number_literal(&self.current_token_bytes))
             }
-            LexerState::StringLiteral => {
+            LexerState::StringLiteral | LexerState::StringLiteralEscape => {
                 self.process_new_token(Token::new_string_literal(&self.current_token_bytes))
             }
             LexerState::Operator => {
@@ -280,10 +492,10 @@ impl Lexer {
             LexerState::VariableIdentifier => {
                 self.process_new_token(Token::new_variable_identifier(&self.current_token_bytes))
             }
-            LexerState::ScopeStart => {
-                self.process_new_token(Token::new_scope_start())
-            }
+            LexerState::ScopeStart => self.process_new_token(Token::new_scope_start()),
             LexerState::ScopeEnd => self.process_new_token(Token::new_scope_end()),
+            LexerState::BlockStart => self.process_new_token(Token::new_block_start()),
+            LexerState::BlockEnd => self.process_new_token(Token::new_block_end()),
         };
 
         // Clear current token
@@ -294,7 +506,18 @@ impl Lexer {
     fn process_new_token(&mut self, token: Result<Token, String>) -> EndTokenResult {
         match token {
             Ok(token) => {
-                self.token_list.push(token);
+                let end = match self.state {
+                    LexerState::NumberLiteral
+                    | LexerState::Operator
+                    | LexerState::VariableIdentifier => self.previous_position,
+                    _ => self.scanner.current_position(),
+                };
+                self.token_list.push(SourceToken {
+                    token,
+                    position: self.current_token_position,
+                    end,
+                    file: None,
+                });
                 self.current_token_bytes.clear();
                 EndTokenResult::Valid
             }
@@ -1,6 +1,11 @@
+use crate::lexer::Position;
+
 pub struct CharScanner {
     chars: Vec<char>,
     position: usize,
+    /// Line and column of the most recently scanned character
+    line: usize,
+    column: usize,
 }
 
 impl CharScanner {
@@ -9,24 +14,21 @@ impl CharScanner {
         Self {
             chars: input.chars().collect(),
             position: 0,
+            line: 1,
+            column: 0,
         }
     }
 
-    pub fn peek(&self) -> Option<char> {
-        self.chars.get(self.position).copied()
+    /// Position of the character most recently returned by `next()`
+    pub fn current_position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
     }
 
-    pub fn peek_offset(&self, offset: isize) -> Option<char> {
-        // @NOTE -1 to match caller expectations
-        // Because `position` has already been incremented by next()
-        //  peek_offset(1)  = peek()    = the next character
-        //  peek_offset(2)  =           = the character after next
-        //  peek_offset(-1) =           = previous character before current
-        let i = (self.position as isize) + offset - 1;
-        if i < 0 {
-            return None;
-        }
-        self.chars.get(i as usize).copied()
+    pub fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
     }
 }
 
@@ -35,6 +37,12 @@ impl Iterator for CharScanner {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.position < self.chars.len() {
+            if self.position > 0 && self.chars[self.position - 1] == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
             let ch = self.chars[self.position];
             self.position += 1;
             Some(ch)
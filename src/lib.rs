@@ -0,0 +1,4 @@
+pub mod execution_context;
+pub mod interpreter;
+pub mod lexer;
+pub mod preprocessor;
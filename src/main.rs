@@ -1,156 +1,480 @@
-use clap::Parser;
-use std::fs;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
 
-use crate::{
-    interpreter::Interpreter,
-    lexer::{Lexer, Token},
+use gnarly_interpreter::{
+    execution_context::capabilities::{Capabilities, FilesystemAccess},
+    execution_context::scope::Scope,
+    interpreter::{
+        Interpreter, InterpreterConfig, Operand,
+        error::{ErrorKind, RuntimeError},
+        limits::ResourceLimits,
+    },
+    lexer::{Lexer, SourceToken},
+    preprocessor::{PreprocessedSource, Preprocessor},
 };
 
-mod execution_context;
-mod interpreter;
-mod lexer;
+use crate::commands::{debug::DebugArgs, lex::LexArgs, test::TestArgs, trace::TraceFormat};
+
+mod commands;
+
+/// The program ran to the end, or called `0 exit`
+const EXIT_SUCCESS: i32 = 0;
+/// The program failed while running e.g. a type error or a failed assertion, or a
+/// subcommand failed, e.g. `test` with a failing test
+const EXIT_RUNTIME_ERROR: i32 = 1;
+/// The program could not be read, preprocessed or lexed, so nothing ran. (2 is what clap
+/// exits with for invalid arguments.)
+const EXIT_SOURCE_ERROR: i32 = 3;
+/// The program raised an error with `throw` and nothing caught it
+const EXIT_UNCAUGHT_THROW: i32 = 4;
+
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0    Success
+  1    Runtime error, or a failing test for `test`
+  2    Invalid command line arguments
+  3    Error reading, preprocessing or lexing the program
+  4    Uncaught throw
+  The program can also choose its own with `code exit`";
 
 #[derive(Parser)]
 #[command(bin_name = "gnarly")]
 #[command(name = "Gnarly")]
 #[command(about = "The Gnarly language interpreter")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
+#[command(after_help = EXIT_CODES_HELP)]
 struct Cli {
-    #[arg(help = "Path to the program's main entrypoint. Start REPL if not provided.")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        help = "Path to the program's main entrypoint, or - to read it from stdin. Start REPL if not provided (and stdin is a terminal)."
+    )]
     file: Option<PathBuf>,
+
+    #[arg(
+        short = 'e',
+        long = "eval",
+        value_name = "CODE",
+        conflicts_with = "file",
+        // Code can start with a negative number e.g. `-e '-1 print'`
+        allow_hyphen_values = true,
+        help = "Run CODE instead of a file"
+    )]
+    eval: Option<String>,
+
+    #[arg(
+        last = true,
+        value_name = "ARGS",
+        help = "Arguments for the program, available to it as the $args array"
+    )]
+    args: Vec<String>,
+
+    #[arg(
+        short = 'I',
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Also look for imported files in DIR (can be repeated)"
+    )]
+    module_path: Vec<PathBuf>,
+
+    #[arg(
+        short = 'D',
+        long = "define",
+        global = true,
+        value_name = "SYMBOL[=VALUE]",
+        help = "Define a symbol for the preprocessor's @if (and @define it, if a value is given)"
+    )]
+    define: Vec<String>,
+
+    #[command(flatten)]
+    diagnostics: DiagnosticArgs,
+
+    #[command(flatten)]
+    sandbox: SandboxArgs,
+
+    #[command(flatten)]
+    limits: LimitArgs,
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// Run *_test.gnarly files and `test "name" { ... }` blocks
+    Test(TestArgs),
+    /// Run a program one token at a time, with breakpoints
+    Debug(DebugArgs),
+    /// Serve the Debug Adapter Protocol over stdio, for debugging from an editor
+    Dap,
+    /// Print the tokens of a program as JSON
+    Lex(LexArgs),
+}
+
+/// Capability flags. Everything is allowed unless `--deny-all` is passed, and a `--deny-*`
+/// flag always wins over the matching `--allow-*` flag.
+#[derive(Args)]
+#[command(next_help_heading = "Sandbox")]
+struct SandboxArgs {
+    #[arg(
+        long,
+        global = true,
+        help = "Deny all capabilities, except those granted with --allow-*"
+    )]
+    deny_all: bool,
+
+    #[arg(long, global = true, help = "Allow printing to stdout and stderr")]
+    allow_stdout: bool,
+    #[arg(long, global = true, help = "Deny printing to stdout and stderr")]
+    deny_stdout: bool,
+
+    #[arg(long, global = true, help = "Allow reading from stdin")]
+    allow_stdin: bool,
+    #[arg(long, global = true, help = "Deny reading from stdin")]
+    deny_stdin: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Only allow filesystem access inside PATH (can be repeated)"
+    )]
+    allow_fs: Vec<PathBuf>,
+    #[arg(long, global = true, help = "Deny all filesystem access")]
+    deny_fs: bool,
+
+    #[arg(long, global = true, help = "Allow reading environment variables")]
+    allow_env: bool,
+    #[arg(long, global = true, help = "Deny reading environment variables")]
+    deny_env: bool,
+
+    #[arg(long, global = true, help = "Allow running other processes")]
+    allow_process: bool,
+    #[arg(long, global = true, help = "Deny running other processes")]
+    deny_process: bool,
 
-    match cli.file {
-        Some(file_path) => {
-            run_file(file_path);
+    #[arg(long, global = true, help = "Allow reading the system clock")]
+    allow_time: bool,
+    #[arg(long, global = true, help = "Deny reading the system clock")]
+    deny_time: bool,
+
+    #[arg(long, global = true, help = "Allow generating random numbers")]
+    allow_random: bool,
+    #[arg(long, global = true, help = "Deny generating random numbers")]
+    deny_random: bool,
+}
+
+impl SandboxArgs {
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = if self.deny_all {
+            Capabilities::none()
+        } else {
+            Capabilities::all()
+        };
+
+        let apply = |current: bool, allow: bool, deny: bool| (current || allow) && !deny;
+        capabilities.stdout = apply(capabilities.stdout, self.allow_stdout, self.deny_stdout);
+        capabilities.stdin = apply(capabilities.stdin, self.allow_stdin, self.deny_stdin);
+        capabilities.env = apply(capabilities.env, self.allow_env, self.deny_env);
+        capabilities.process = apply(capabilities.process, self.allow_process, self.deny_process);
+        capabilities.time = apply(capabilities.time, self.allow_time, self.deny_time);
+        capabilities.random = apply(capabilities.random, self.allow_random, self.deny_random);
+
+        if self.deny_fs {
+            capabilities.filesystem = FilesystemAccess::None;
+        } else if !self.allow_fs.is_empty() {
+            capabilities.filesystem = FilesystemAccess::Paths(self.allow_fs.clone());
         }
-        None => {
-            run_repl();
+
+        capabilities
+    }
+}
+
+/// Ways of looking at what a program does, or how it is read.
+#[derive(Args)]
+#[command(next_help_heading = "Diagnostics")]
+struct DiagnosticArgs {
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "human",
+        help = "Log every token executed, with the stack before and after it, to stderr"
+    )]
+    trace: Option<TraceFormat>,
+
+    #[arg(long, help = "Print the tokens of the program instead of running it")]
+    dump_tokens: bool,
+
+    #[arg(
+        long,
+        help = "Print the blocks and scopes of the program as a tree instead of running it"
+    )]
+    dump_ast: bool,
+}
+
+/// Resource limits. Everything is unlimited unless specified.
+#[derive(Args)]
+#[command(next_help_heading = "Limits")]
+struct LimitArgs {
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        help = "Maximum number of tokens to execute"
+    )]
+    max_steps: Option<u64>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        help = "Maximum number of operands on the stack"
+    )]
+    max_stack: Option<usize>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        help = "Maximum depth of nested scopes"
+    )]
+    max_depth: Option<usize>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "BYTES",
+        help = "Maximum bytes of strings and arrays to allocate"
+    )]
+    max_memory: Option<usize>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "MS",
+        help = "Maximum running time in milliseconds"
+    )]
+    max_time: Option<u64>,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            max_steps: self.max_steps,
+            max_stack_size: self.max_stack,
+            max_scope_depth: self.max_depth,
+            max_memory: self.max_memory,
+            max_duration: self.max_time.map(Duration::from_millis),
         }
     }
 }
 
-fn run_file(file_path: PathBuf) {
-    // Read the file contents
-    let file_contents = match fs::read_to_string(&file_path) {
-        Ok(contents) => contents,
-        Err(err) => {
-            eprintln!("Error reading file '{}': {}", file_path.display(), err);
-            process::exit(1);
+fn main() {
+    let cli = Cli::parse();
+
+    let config = InterpreterConfig {
+        capabilities: cli.sandbox.capabilities(),
+        limits: cli.limits.limits(),
+        module_paths: cli.module_path,
+    };
+
+    if let Some(command) = cli.command {
+        let result = match command {
+            Command::Test(args) => {
+                commands::test::run(args, &cli.define, config).map(success_exit_code)
+            }
+            Command::Debug(args) => {
+                let tokens = read_program_or_exit(&args.file, &cli.define, &config.capabilities);
+                commands::debug::run(args, tokens, config)
+            }
+            Command::Dap => commands::dap::run(&cli.define, config).map(success_exit_code),
+            Command::Lex(args) => {
+                let tokens = read_program_or_exit(&args.file, &cli.define, &config.capabilities);
+                commands::lex::run(tokens).map(success_exit_code)
+            }
+        };
+        match result {
+            Ok(code) => exit(code),
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(EXIT_RUNTIME_ERROR);
+            }
         }
+    }
+
+    let program = match (cli.eval, cli.file) {
+        (Some(code), _) => Program::Code(code),
+        (None, Some(file_path)) if file_path == Path::new("-") => Program::Stdin,
+        (None, Some(file_path)) => Program::File(file_path),
+        (None, None) if !io::stdin().is_terminal() => Program::Stdin,
+        (None, None) => exit(commands::repl::run(config)),
     };
+    exit(run_program(
+        program,
+        &cli.args,
+        &cli.define,
+        config,
+        &cli.diagnostics,
+    ));
+}
+
+/// Exit the process, first writing out anything the program printed that is still buffered,
+/// as `process::exit` does not.
+fn exit(code: i32) -> ! {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    process::exit(code);
+}
 
-    // @TODO pre-processor
+/// Exit code for a subcommand that reports whether it succeeded.
+fn success_exit_code(success: bool) -> i32 {
+    if success {
+        EXIT_SUCCESS
+    } else {
+        EXIT_RUNTIME_ERROR
+    }
+}
 
-    // Lex file
-    let lexer_result = match Lexer::scan(&file_contents) {
-        Ok(lexer) => lexer,
+/// Exit code for a program that stopped with `err`.
+fn error_exit_code(err: &RuntimeError) -> i32 {
+    match err.kind {
+        ErrorKind::Exit(code) => code,
+        ErrorKind::Thrown => EXIT_UNCAUGHT_THROW,
+        _ => EXIT_RUNTIME_ERROR,
+    }
+}
+
+/// Where the program to run comes from.
+enum Program {
+    File(PathBuf),
+    /// Read until the end of stdin, e.g. `echo '1 print' | gnarly`
+    Stdin,
+    /// Given with `-e`
+    Code(String),
+}
+
+fn run_program(
+    program: Program,
+    args: &[String],
+    symbols: &[String],
+    config: InterpreterConfig,
+    diagnostics: &DiagnosticArgs,
+) -> i32 {
+    let token_list = match program {
+        Program::File(file_path) => read_program(&file_path, symbols, &config.capabilities),
+        Program::Stdin => {
+            let mut source = String::new();
+            match io::stdin().read_to_string(&mut source) {
+                Ok(_) => read_source(Path::new("<stdin>"), &source, symbols, &config.capabilities),
+                Err(err) => Err(format!("Error reading stdin: {}", err)),
+            }
+        }
+        Program::Code(code) => {
+            read_source(Path::new("<eval>"), &code, symbols, &config.capabilities)
+        }
+    };
+    let token_list = match token_list {
+        Ok(token_list) => token_list,
         Err(err) => {
-            eprintln!("Lexer error: {}", err);
-            process::exit(1);
+            eprintln!("{}", err);
+            return EXIT_SOURCE_ERROR;
         }
     };
 
-    // @DEBUG Dump tokens
-    println!("Tokens:");
-    for token in lexer_result.token_list.iter() {
-        // @TODO token string function
-        match token {
-            Token::NumberLiteral(value) => print!("[Number({})]", value),
-            Token::StringLiteral(value) => print!("[String(\"{}\")]", value.replace("\n", "\\n")),
-            Token::Operator(op) => print!("[Operator({})]", op),
-            Token::VariableIdentifier(var_name) => print!("[Variable(${var_name})]"),
-            Token::ScopeStart => print!("[ScopeStart]"),
-            Token::ScopeEnd => print!("[ScopeEnd]"),
+    if diagnostics.dump_tokens {
+        commands::lex::print_tokens(&token_list);
+        return EXIT_SUCCESS;
+    }
+    if diagnostics.dump_ast {
+        if let Err(err) = commands::lex::print_tree(&token_list) {
+            eprintln!("Error: {}", err);
+            return EXIT_SOURCE_ERROR;
         }
-        print!(" ")
+        return EXIT_SUCCESS;
     }
-    println!();
 
     // Run interpreter
-    let mut interpreter = Interpreter::new();
-    match interpreter.run(lexer_result.token_list) {
-        Ok(_) => { /* 😎 */ }
+    let mut interpreter = Interpreter::new(config);
+    let args = args.iter().cloned().map(Operand::String).collect();
+    interpreter.context.set_variable(
+        "args".to_string(),
+        Operand::Scope(Scope::from_operands(args)),
+    );
+    if let Some(format) = diagnostics.trace {
+        interpreter.set_tracer(commands::trace::tracer(format));
+    }
+    let result = interpreter.run(token_list);
+    let _ = interpreter.context.output.stdout().flush();
+    match result {
+        Ok(_) => EXIT_SUCCESS, // 😎
+        // Not an error, so nothing to report
+        Err(err) if matches!(err.kind, ErrorKind::Exit(_)) => error_exit_code(&err),
         Err(err) => {
             eprintln!("Error: {}", err);
-            process::exit(1);
+            for frame in &err.trace {
+                eprintln!("    at {}", frame);
+            }
+            error_exit_code(&err)
         }
     }
 }
 
-fn run_repl() {
-    println!("Gnarly REPL v{}", env!("CARGO_PKG_VERSION"));
-    println!("Type '.exit' to quit");
-
-    let mut interpreter = Interpreter::new();
-
-    loop {
-        print!("gnarly> ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                let input = input.trim();
-
-                // Handle special commands
-                if input == ".exit" {
-                    println!("Have a gnarly day!");
-                    break;
-                }
-
-                // Skip empty lines
-                if input.is_empty() {
-                    continue;
-                }
-
-                // Lex the input
-                let lexer_result = match Lexer::scan(input) {
-                    Ok(lexer) => lexer,
-                    Err(err) => {
-                        eprintln!("Lexer error: {}", err);
-                        continue;
-                    }
-                };
-
-                // Run the tokens through interpreter
-                let operand_stack_size = interpreter
-                    .context
-                    .current_scope_readonly()
-                    .get_operand_stack()
-                    .len() as isize;
-                match interpreter.run(lexer_result.token_list) {
-                    Ok(_) => {
-                        // Print most recent operand, if any pushed to the stack
-                        let operand_stack = interpreter
-                            .context
-                            .current_scope_readonly()
-                            .get_operand_stack();
-                        let operand_stack_delta = operand_stack.len() as isize - operand_stack_size;
-                        if operand_stack_delta > 0 {
-                            println!(
-                                "{}",
-                                interpreter
-                                    .context
-                                    .operand_display(operand_stack.last().unwrap())
-                            )
-                        }
-                    }
-                    Err(err) => {
-                        eprintln!("Error: {}", err);
-                    }
-                }
-            }
-            Err(err) => {
-                eprintln!("Error reading input: {}", err);
-                break;
-            }
+/// Preprocess and lex a program, with token positions pointing at the original files.
+/// `capabilities` decide which files it may `@include`.
+fn read_program(
+    file_path: &Path,
+    symbols: &[String],
+    capabilities: &Capabilities,
+) -> Result<Vec<SourceToken>, String> {
+    // Pre-process (this also reads the file contents)
+    let preprocessed = Preprocessor::new(symbols, capabilities).process_file(file_path);
+    lex_program(preprocessed)
+}
+
+/// `read_program` for subcommands, exiting if the program cannot be read.
+fn read_program_or_exit(
+    file_path: &Path,
+    symbols: &[String],
+    capabilities: &Capabilities,
+) -> Vec<SourceToken> {
+    match read_program(file_path, symbols, capabilities) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(EXIT_SOURCE_ERROR);
         }
     }
 }
+
+/// Like `read_program`, for source code that is not in a file. `name` is used in its place.
+fn read_source(
+    name: &Path,
+    source: &str,
+    symbols: &[String],
+    capabilities: &Capabilities,
+) -> Result<Vec<SourceToken>, String> {
+    lex_program(Preprocessor::new(symbols, capabilities).process_source(name, source))
+}
+
+fn lex_program(
+    preprocessed: Result<PreprocessedSource, String>,
+) -> Result<Vec<SourceToken>, String> {
+    let preprocessed = preprocessed.map_err(|err| format!("Preprocessor error: {}", err))?;
+
+    // Lex file
+    let mut lexer_result = Lexer::scan(&preprocessed.source).map_err(|err| {
+        match preprocessed.original_line(err.position.line) {
+            Some((file, line)) => format!(
+                "Lexer error: {} ({}, line {}, column {})",
+                err.message,
+                file.display(),
+                line,
+                err.position.column
+            ),
+            None => format!("Lexer error: {}", err),
+        }
+    })?;
+    preprocessed.map_tokens(&mut lexer_result.token_list);
+    Ok(lexer_result.token_list)
+}
@@ -0,0 +1,304 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    execution_context::capabilities::Capabilities,
+    lexer::{Position, SourceToken},
+};
+
+// @NOTE Directives are lines starting with `@`:
+//  - `@include "file"` inserts another file, relative to the current one
+//  - `@define NAME value` replaces every word `NAME` (outside of strings) with `value`
+//  - `@if NAME` / `@if !NAME`, `@else`, `@endif` keep or drop lines depending on whether
+//    `NAME` has been defined, either with `@define` or on the command line
+// Included files are read like `file.read` would, so they have to be allowed by the
+// filesystem capability. The file being processed is not checked, as it was asked for directly.
+// Directive lines are removed from the output, so a line map is kept to point diagnostics
+// back at the original file and line. Columns are not adjusted for substitutions.
+
+/// Characters that are tokens of their own, so they also end a word that could be replaced.
+const BRACKETS: [char; 4] = ['{', '}', '[', ']'];
+
+/// Source code after preprocessing, ready to be lexed.
+pub struct PreprocessedSource {
+    pub source: String,
+    /// Original file and line of each line of `source`
+    line_map: Vec<(Arc<Path>, usize)>,
+}
+
+impl PreprocessedSource {
+    /// Original file and line of a (1-based) line in the preprocessed source.
+    pub fn original_line(&self, line: usize) -> Option<(&Arc<Path>, usize)> {
+        let (file, original_line) = self.line_map.get(line.checked_sub(1)?)?;
+        Some((file, *original_line))
+    }
+
+    /// Point the positions of tokens lexed from `source` back at the original files.
+    pub fn map_tokens(&self, tokens: &mut [SourceToken]) {
+        for source_token in tokens {
+            if let Some((file, line)) = self.original_line(source_token.position.line) {
+                source_token.file = Some(file.clone());
+                source_token.position = Position {
+                    line,
+                    column: source_token.position.column,
+                };
+            }
+            // Strings can end on a later line
+            if let Some((_, line)) = self.original_line(source_token.end.line) {
+                source_token.end = Position {
+                    line,
+                    column: source_token.end.column,
+                };
+            }
+        }
+    }
+}
+
+/// State of one `@if` while its section is being read.
+struct Conditional {
+    /// Whether the lines of the section currently being read are kept
+    active: bool,
+    /// Whether the enclosing section is being kept
+    parent_active: bool,
+    seen_else: bool,
+    /// Where the `@if` is, for reporting a missing `@endif`
+    line: usize,
+}
+
+pub struct Preprocessor {
+    /// Defined symbols and their replacement, if they have one
+    definitions: HashMap<String, Option<String>>,
+    /// Files currently being read, outermost first, for detecting `@include` cycles
+    including: Vec<PathBuf>,
+    /// Which files `@include` may read
+    capabilities: Capabilities,
+    output: String,
+    line_map: Vec<(Arc<Path>, usize)>,
+}
+
+impl Preprocessor {
+    /// `symbols` are defined before reading any files, either as `NAME` or `NAME=value`.
+    pub fn new(symbols: &[String], capabilities: &Capabilities) -> Self {
+        let definitions = symbols
+            .iter()
+            .map(|symbol| match symbol.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (symbol.clone(), None),
+            })
+            .collect();
+        Self {
+            definitions,
+            including: Vec::new(),
+            capabilities: capabilities.clone(),
+            output: String::new(),
+            line_map: Vec::new(),
+        }
+    }
+
+    pub fn process_file(mut self, path: &Path) -> Result<PreprocessedSource, String> {
+        self.include_file(path)?;
+        Ok(self.finish())
+    }
+
+    /// Process source code that is not read from a file, e.g. from stdin. `name` stands in for
+    /// the file in positions, and `@include` paths are relative to the working directory.
+    pub fn process_source(
+        mut self,
+        name: &Path,
+        source: &str,
+    ) -> Result<PreprocessedSource, String> {
+        self.process_lines(name, source)?;
+        Ok(self.finish())
+    }
+
+    fn finish(self) -> PreprocessedSource {
+        PreprocessedSource {
+            source: self.output,
+            line_map: self.line_map,
+        }
+    }
+
+    fn include_file(&mut self, path: &Path) -> Result<(), String> {
+        let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.including.contains(&canonical_path) {
+            return Err(format!("Cannot include '{}' inside itself", path.display()));
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Error reading file '{}': {}", path.display(), err))?;
+
+        self.including.push(canonical_path);
+        let result = self.process_lines(path, &contents);
+        self.including.pop();
+        result
+    }
+
+    fn process_lines(&mut self, path: &Path, contents: &str) -> Result<(), String> {
+        let file: Arc<Path> = Arc::from(path);
+        let error = |line: usize, message: String| {
+            format!("{} (line {}): {}", path.display(), line, message)
+        };
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        // Strings can span several lines, and directives inside them are just text
+        let mut in_string = false;
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let active = conditionals
+                .last()
+                .is_none_or(|conditional| conditional.active);
+            let trimmed = line.trim_start();
+
+            if in_string || !trimmed.starts_with('@') {
+                if active {
+                    let (substituted, still_in_string) = self.substitute(line, in_string);
+                    in_string = still_in_string;
+                    self.output.push_str(&substituted);
+                    self.output.push('\n');
+                    self.line_map.push((file.clone(), line_number));
+                }
+                continue;
+            }
+
+            let (directive, argument) = match trimmed.split_once(char::is_whitespace) {
+                Some((directive, argument)) => (directive, argument.trim()),
+                None => (trimmed, ""),
+            };
+            match directive {
+                "@if" => {
+                    let (negated, symbol) = match argument.strip_prefix('!') {
+                        Some(symbol) => (true, symbol.trim()),
+                        None => (false, argument),
+                    };
+                    if symbol.is_empty() {
+                        return Err(error(
+                            line_number,
+                            "Expected a symbol after @if".to_string(),
+                        ));
+                    }
+                    let condition = self.definitions.contains_key(symbol) != negated;
+                    conditionals.push(Conditional {
+                        active: active && condition,
+                        parent_active: active,
+                        seen_else: false,
+                        line: line_number,
+                    });
+                }
+                "@else" => match conditionals.last_mut() {
+                    Some(conditional) if !conditional.seen_else => {
+                        conditional.seen_else = true;
+                        conditional.active = conditional.parent_active && !conditional.active;
+                    }
+                    Some(_) => {
+                        return Err(error(line_number, "Duplicate @else".to_string()));
+                    }
+                    None => {
+                        return Err(error(line_number, "@else without @if".to_string()));
+                    }
+                },
+                "@endif" => {
+                    if conditionals.pop().is_none() {
+                        return Err(error(line_number, "@endif without @if".to_string()));
+                    }
+                }
+                // Other directives only apply in sections that are kept
+                _ if !active => {}
+                "@define" => {
+                    let (name, value) = match argument.split_once(char::is_whitespace) {
+                        Some((name, value)) => (name, Some(value.trim().to_string())),
+                        None => (argument, None),
+                    };
+                    if name.is_empty() {
+                        return Err(error(
+                            line_number,
+                            "Expected a name after @define".to_string(),
+                        ));
+                    }
+                    self.definitions.insert(name.to_string(), value);
+                }
+                "@include" => {
+                    let Some(include_path) = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                    else {
+                        return Err(error(
+                            line_number,
+                            format!("Expected @include \"file\" but found: {}", argument),
+                        ));
+                    };
+                    let base = path.parent().unwrap_or_else(|| Path::new(""));
+                    let include_path = base.join(include_path);
+                    if !self
+                        .capabilities
+                        .allows_path(&include_path.display().to_string())
+                    {
+                        return Err(error(
+                            line_number,
+                            format!(
+                                "Permission denied: '@include' is not allowed to access '{}'",
+                                include_path.display()
+                            ),
+                        ));
+                    }
+                    self.include_file(&include_path)
+                        .map_err(|err| error(line_number, err))?;
+                }
+                _ => {
+                    return Err(error(
+                        line_number,
+                        format!("Unknown directive: {}", directive),
+                    ));
+                }
+            }
+        }
+
+        if let Some(conditional) = conditionals.last() {
+            return Err(error(
+                conditional.line,
+                "@if without matching @endif".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Replace defined words outside of strings. Words end wherever a token would, so e.g.
+    /// `{GREETING}` is replaced too. Returns the new line, and whether it ends inside a string.
+    fn substitute(&self, line: &str, mut in_string: bool) -> (String, bool) {
+        let mut result = String::with_capacity(line.len());
+        let mut word = String::new();
+        let mut escaped = false;
+
+        for ch in line.chars() {
+            if in_string {
+                result.push(ch);
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == '"' {
+                    in_string = false;
+                }
+            } else if ch.is_whitespace() || ch == '"' || BRACKETS.contains(&ch) {
+                self.push_word(&mut result, &mut word);
+                result.push(ch);
+                in_string = ch == '"';
+            } else {
+                word.push(ch);
+            }
+        }
+        self.push_word(&mut result, &mut word);
+        (result, in_string)
+    }
+
+    fn push_word(&self, result: &mut String, word: &mut String) {
+        match self.definitions.get(word.as_str()) {
+            Some(Some(value)) => result.push_str(value),
+            _ => result.push_str(word),
+        }
+        word.clear();
+    }
+}
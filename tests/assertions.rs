@@ -0,0 +1,41 @@
+use gnarly_interpreter::{
+    interpreter::{Interpreter, InterpreterConfig, error::RuntimeError},
+    lexer::Lexer,
+};
+
+fn run(source: &str) -> Result<(), RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter
+        .run(Lexer::scan(source).unwrap().token_list)
+        .map(|_| ())
+}
+
+#[test]
+fn passing_assertions() {
+    run("1 assert").unwrap();
+    run("\"a\" \"a\" assert.eq { 1 { 2 $x set } } { 1 { 2 $x set } } assert.eq").unwrap();
+    run("1 2 assert.neq").unwrap();
+    run("[ \"oops\" throw ] assert.throws").unwrap();
+}
+
+#[test]
+fn assert_eq_reports_first_difference() {
+    let err = run("{ 1 { 5 $x set } } { 1 { 6 $x set } } assert.eq").unwrap_err();
+
+    assert!(
+        err.message
+            .ends_with("first difference at [1].x: expected 6, actual 5"),
+        "{}",
+        err.message
+    );
+}
+
+#[test]
+fn assert_throws_fails_when_block_succeeds() {
+    let err = run("[ 1 ] assert.throws").unwrap_err();
+
+    assert_eq!(
+        err.message,
+        "assert.throws: Expected block to throw an error"
+    );
+}
@@ -0,0 +1,138 @@
+//! Ways of handing a program to the `gnarly` binary other than a file, and what it exits with.
+
+use std::{
+    env, fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn gnarly(args: &[&str], stdin: Option<&str>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Dropping stdin closes it, so the program sees the end of its input
+    let mut child_stdin = child.stdin.take().unwrap();
+    child_stdin
+        .write_all(stdin.unwrap_or("").as_bytes())
+        .unwrap();
+    drop(child_stdin);
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn eval_runs_code_from_the_command_line() {
+    let output = gnarly(&["-e", "1 2 + print"], None);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n");
+
+    // Code that starts like a flag is still code
+    let output = gnarly(&["-e", "-1 print"], None);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "-1\n");
+    let output = gnarly(&["--eval", "-2.5 -1 + print"], None);
+    assert_eq!(stdout(&output), "-3.5\n");
+
+    let output = gnarly(&["-e", "1 +"], None);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<eval>, line 1, column 3"));
+}
+
+#[test]
+fn program_is_read_from_stdin() {
+    let output = gnarly(&["-"], Some("\"dash\" print"));
+    assert_eq!(stdout(&output), "dash\n");
+
+    // Not a terminal, so no REPL
+    let output = gnarly(&[], Some("\"piped\" print"));
+    assert_eq!(stdout(&output), "piped\n");
+}
+
+#[test]
+fn arguments_after_double_dash_are_in_args() {
+    let output = gnarly(&["-e", "$args print", "--", "a", "b c"], None);
+    assert_eq!(stdout(&output), "{ \"a\", \"b c\" }\n");
+
+    let output = gnarly(&["-", "--", "-e"], Some("$args print"));
+    assert_eq!(stdout(&output), "{ \"-e\" }\n");
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    let code = |source: &str| gnarly(&["-e", source], None).status.code();
+    assert_eq!(code("1 print"), Some(0));
+    assert_eq!(code("1 +"), Some(1));
+    assert_eq!(code("$"), Some(3));
+    assert_eq!(code("\"oops\" throw"), Some(4));
+    assert_eq!(code("[ 42 exit ] [ ] try"), Some(42));
+    assert_eq!(code("256 exit"), Some(1));
+}
+
+#[test]
+fn subcommands_use_the_same_exit_codes() {
+    let dir = env::temp_dir().join(format!("gnarly_cli_exit_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str, source: &str| {
+        let path = dir.join(name);
+        fs::write(&path, source).unwrap();
+        path.display().to_string()
+    };
+    let unlexable = file("unlexable.gnarly", "$\n");
+    let exits = file("exits.gnarly", "1 print\n0 exit\n");
+    let exits_with_7 = file("exits_with_7.gnarly", "7 exit\n");
+    let fails = file("fails.gnarly", "1 +\n");
+
+    for command in ["debug", "lex"] {
+        let output = gnarly(&[command, &unlexable], None);
+        assert_eq!(output.status.code(), Some(3), "{}", command);
+    }
+    let output = gnarly(&["debug", "missing.gnarly"], None);
+    assert_eq!(output.status.code(), Some(3));
+
+    // `exit` is not an error in the debugger either
+    let output = gnarly(&["debug", &exits], Some("continue\n"));
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("Program exited with code 0"));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Error"));
+    let output = gnarly(&["debug", &exits_with_7], Some("continue\n"));
+    assert_eq!(output.status.code(), Some(7));
+    let output = gnarly(&["debug", &fails], Some("continue\n"));
+    assert_eq!(output.status.code(), Some(1));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn output_is_flushed_before_exiting() {
+    let output = gnarly(&["-e", "\"no newline\" print.no_newline 5 exit"], None);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(stdout(&output), "no newline");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn dump_ast_prints_nothing_for_a_program_that_does_not_parse() {
+    let output = gnarly(&["--dump-ast", "-e", "[ 1 { } ] print"], None);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "block (<eval>:1:1)\n  number 1 (<eval>:1:3)\n  scope (<eval>:1:5)\n\
+         operator print (<eval>:1:11)\n"
+    );
+
+    let output = gnarly(&["--dump-ast", "-e", "[ 1 2 + } ]"], None);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Unexpected '}' (<eval>:1:9)"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
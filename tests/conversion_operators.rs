@@ -0,0 +1,69 @@
+use gnarly_interpreter::{
+    interpreter::{Interpreter, InterpreterConfig, error::RuntimeError},
+    lexer::Lexer,
+};
+
+/// The operands `source` leaves on the stack, as `print` would show them.
+fn stack(source: &str) -> Result<Vec<String>, RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.run(Lexer::scan(source).unwrap().token_list)?;
+    let context = &interpreter.context;
+    Ok(context
+        .current_scope_readonly()
+        .get_operand_stack()
+        .iter()
+        .map(|operand| context.operand_to_string(operand).unwrap())
+        .collect())
+}
+
+fn error(source: &str) -> String {
+    stack(source).unwrap_err().message
+}
+
+#[test]
+fn conversions() {
+    assert_eq!(
+        stack("\"2.5\" to.number -2.7 to.int \"-2.7\" to.int 3 to.string").unwrap(),
+        ["2.5", "-2", "-2", "3"]
+    );
+    assert_eq!(
+        stack("1 to.boolean \"\" to.boolean \"false\" to.boolean").unwrap(),
+        ["true", "false", "true"]
+    );
+    assert!(error("\"abc\" to.number").contains("abc"));
+}
+
+#[test]
+fn number_format() {
+    assert_eq!(
+        stack("3.14159 2 8 number.format 3.14159 0 0 number.format -2.5 3 0 number.format")
+            .unwrap(),
+        ["    3.14", "3", "-2.500"]
+    );
+    // Width is a minimum
+    assert_eq!(stack("12345.678 1 3 number.format").unwrap(), ["12345.7"]);
+    assert_eq!(
+        stack("1 0 65535 number.format string.length").unwrap(),
+        ["65535"]
+    );
+}
+
+#[test]
+fn number_format_rejects_out_of_range_arguments() {
+    assert_eq!(
+        error("3.14 2 70000 number.format"),
+        "number.format: Expected width to be at most 65535 but found: 70000"
+    );
+    assert_eq!(
+        error("3.14 70000 2 number.format"),
+        "number.format: Expected precision to be at most 65535 but found: 70000"
+    );
+    assert_eq!(
+        error("3.14 2 -1 number.format"),
+        "number.format: Expected width to be a non-negative integer but found: -1"
+    );
+    assert_eq!(
+        error("3.14 1.5 2 number.format"),
+        "number.format: Expected precision to be a non-negative integer but found: 1.5"
+    );
+}
@@ -0,0 +1,280 @@
+//! Drives `gnarly dap` with a scripted Debug Adapter Protocol client.
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use serde_json::{Value, json};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    /// Events received while waiting for something else
+    events: Vec<Value>,
+    seq: i64,
+}
+
+impl Client {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+            .arg("dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            loop {
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if stdout.read_line(&mut line).unwrap() == 0 {
+                        return;
+                    }
+                    match line.trim_end().strip_prefix("Content-Length: ") {
+                        Some(length) => content_length = length.parse().unwrap(),
+                        None if line.trim_end().is_empty() => break,
+                        None => {}
+                    }
+                }
+                let mut body = vec![0; content_length];
+                stdout.read_exact(&mut body).unwrap();
+                if sender.send(serde_json::from_slice(&body).unwrap()).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            child,
+            stdin,
+            messages,
+            events: Vec::new(),
+            seq: 0,
+        }
+    }
+
+    fn receive(&mut self) -> Value {
+        self.messages
+            .recv_timeout(TIMEOUT)
+            .expect("Timed out waiting for a message from the debug adapter")
+    }
+
+    /// Send a request and return the body of its (successful) response.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let body = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+
+        loop {
+            let message = self.receive();
+            if message["type"] == "response" && message["request_seq"] == self.seq {
+                assert_eq!(message["success"], true, "{} failed: {}", command, message);
+                return message["body"].clone();
+            }
+            self.events.push(message);
+        }
+    }
+
+    fn wait_for_event(&mut self, event: &str) -> Value {
+        if let Some(index) = self
+            .events
+            .iter()
+            .position(|message| message["event"] == event)
+        {
+            return self.events.remove(index)["body"].clone();
+        }
+        loop {
+            let message = self.receive();
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+            self.events.push(message);
+        }
+    }
+
+    /// Everything the program printed to stdout so far, once it has terminated.
+    fn output_until_terminated(&mut self) -> String {
+        self.wait_for_event("terminated");
+        self.events
+            .iter()
+            .filter(|message| message["event"] == "output")
+            .filter(|message| message["body"]["category"] == "stdout")
+            .map(|message| message["body"]["output"].as_str().unwrap())
+            .collect()
+    }
+
+    fn launch(&mut self, program: &PathBuf, stop_on_entry: bool) {
+        self.request("initialize", json!({ "adapterID": "gnarly" }));
+        self.request(
+            "launch",
+            json!({ "program": program, "stopOnEntry": stop_on_entry }),
+        );
+        self.wait_for_event("initialized");
+    }
+
+    fn stack_frames(&mut self) -> Vec<(String, u64, u64)> {
+        let body = self.request("stackTrace", json!({ "threadId": 1 }));
+        body["stackFrames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|frame| {
+                (
+                    frame["name"].as_str().unwrap().to_string(),
+                    frame["line"].as_u64().unwrap(),
+                    frame["column"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn variables(&mut self, reference: &Value) -> Vec<(String, String)> {
+        let body = self.request("variables", json!({ "variablesReference": reference }));
+        body["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variable| {
+                (
+                    variable["name"].as_str().unwrap().to_string(),
+                    variable["value"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn write_program(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("gnarly_dap_{}_{}.gnarly", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn pair(name: &str, value: &str) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+#[test]
+fn breakpoint_shows_stack_scopes_and_variables() {
+    let program = write_program(
+        "breakpoint",
+        "[ 2 * ] \"double\" def\n5 $x set\n$x double print\n\"done\" print\n",
+    );
+    let mut client = Client::start();
+    client.launch(&program, false);
+
+    // Inside the body of `double`, and on a line without code, which moves to the next token
+    let body = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": program },
+            "breakpoints": [{ "line": 1, "column": 3 }, { "line": 5 }],
+        }),
+    );
+    assert_eq!(body["breakpoints"][0]["verified"], true);
+    assert_eq!(body["breakpoints"][0]["column"], 3);
+    assert_eq!(body["breakpoints"][1]["verified"], false);
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.wait_for_event("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(
+        client.stack_frames(),
+        vec![("double".to_string(), 1, 3), ("<main>".to_string(), 3, 4)]
+    );
+
+    let body = client.request("scopes", json!({ "frameId": 0 }));
+    let scopes = body["scopes"].as_array().unwrap();
+    assert_eq!(scopes[0]["name"], "Operand stack");
+    assert_eq!(scopes[1]["name"], "Global");
+    assert_eq!(
+        client.variables(&scopes[0]["variablesReference"]),
+        vec![pair("[0]", "$x (5)")]
+    );
+    assert_eq!(
+        client.variables(&scopes[1]["variablesReference"]),
+        vec![pair("$x", "5")]
+    );
+
+    let body = client.request("evaluate", json!({ "expression": "$x 1 +" }));
+    assert_eq!(body["result"], "6");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.output_until_terminated(), "10\ndone\n");
+    fs::remove_file(program).unwrap();
+}
+
+#[test]
+fn step_over_and_into_user_defined_operators() {
+    let program = write_program("step", "[ 1 + ] \"inc\" def\n1 inc\ninc\n");
+    let mut client = Client::start();
+    client.launch(&program, true);
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.wait_for_event("stopped")["reason"], "entry");
+
+    // `[ 1 + ]`, `"inc"`, `def` and `1` are one token each
+    for _ in 0..4 {
+        client.request("next", json!({ "threadId": 1 }));
+        assert_eq!(client.wait_for_event("stopped")["reason"], "step");
+    }
+    assert_eq!(client.stack_frames(), vec![("<main>".to_string(), 2, 3)]);
+
+    client.request("next", json!({ "threadId": 1 }));
+    client.wait_for_event("stopped");
+    assert_eq!(client.stack_frames(), vec![("<main>".to_string(), 3, 1)]);
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    client.wait_for_event("stopped");
+    assert_eq!(
+        client.stack_frames(),
+        vec![("inc".to_string(), 1, 3), ("<main>".to_string(), 3, 1)]
+    );
+
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.wait_for_event("exited")["exitCode"], 0);
+    client.wait_for_event("terminated");
+    fs::remove_file(program).unwrap();
+}
+
+#[test]
+fn pause_stops_a_running_program() {
+    let program = write_program("pause", "[ forever ] \"forever\" def\nforever\n");
+    let mut client = Client::start();
+    client.launch(&program, false);
+    client.request("configurationDone", json!({}));
+
+    client.request("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.wait_for_event("stopped")["reason"], "pause");
+    let frames = client.stack_frames();
+    assert_eq!(frames.last().unwrap().0, "<main>");
+
+    client.request("disconnect", json!({}));
+    fs::remove_file(program).unwrap();
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex};
+
+use gnarly_interpreter::{
+    execution_context::capabilities::Capabilities,
+    interpreter::{
+        ExecutionStatus, Interpreter, InterpreterConfig, Operand,
+        error::ErrorKind,
+        limits::{Limit, ResourceLimits},
+    },
+    lexer::{Lexer, Position, SourceToken, Token},
+};
+
+fn tokens(source: &str) -> Vec<SourceToken> {
+    Lexer::scan(source).unwrap().token_list
+}
+
+fn paused_at_start(source: &str) -> Interpreter {
+    // `print.stack` needs stdout
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        capabilities: Capabilities::all(),
+        ..InterpreterConfig::default()
+    });
+    assert_eq!(
+        interpreter.run_paused(tokens(source)).unwrap(),
+        ExecutionStatus::Paused
+    );
+    interpreter
+}
+
+fn next_operator(interpreter: &Interpreter) -> Option<String> {
+    match &interpreter.next_token()?.token {
+        Token::Operator(op) => Some(op.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn step_executes_one_token_at_a_time_into_blocks() {
+    let mut interpreter = paused_at_start("[ 1 + ] \"inc\" def\n1 inc print.stack");
+
+    // The whole block is a single token
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    assert_eq!(next_operator(&interpreter).as_deref(), Some("def"));
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    assert_eq!(next_operator(&interpreter).as_deref(), Some("inc"));
+    assert_eq!(interpreter.call_depth(), 0);
+
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.call_depth(), 1);
+    assert_eq!(
+        interpreter.next_token().unwrap().position,
+        Position { line: 1, column: 3 }
+    );
+    let trace: Vec<String> = interpreter
+        .current_stack_trace()
+        .into_iter()
+        .map(|frame| frame.name)
+        .collect();
+    assert_eq!(trace, vec!["inc", "<main>"]);
+
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.call_depth(), 0);
+    assert_eq!(next_operator(&interpreter).as_deref(), Some("print.stack"));
+
+    assert_eq!(interpreter.step().unwrap(), ExecutionStatus::Completed);
+    assert!(interpreter.next_token().is_none());
+}
+
+#[test]
+fn evaluate_leaves_the_paused_stack_alone() {
+    let mut interpreter = paused_at_start("1 $x set 2 3 +");
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+
+    let operands = interpreter.evaluate(tokens("$x 10 *")).unwrap();
+    assert!(matches!(operands.as_slice(), [Operand::Number(value)] if *value == 10.0));
+
+    // Assigning an existing variable is visible to the rest of the run
+    interpreter.evaluate(tokens("5 $x set")).unwrap();
+    assert!(matches!(
+        interpreter.context.get_variable(&"x".to_string()),
+        Some(Operand::Number(value)) if *value == 5.0
+    ));
+
+    assert!(interpreter.evaluate(tokens("}")).is_err());
+    assert!(interpreter.evaluate(tokens("\"oops\" throw")).is_err());
+
+    // The run carries on from where it was paused
+    assert_eq!(interpreter.resume().unwrap(), ExecutionStatus::Completed);
+    let stack = interpreter
+        .context
+        .current_scope_readonly()
+        .get_operand_stack();
+    assert!(matches!(stack.as_slice(), [Operand::Number(value)] if *value == 5.0));
+}
+
+#[test]
+fn evaluate_counts_memory_on_top_of_the_paused_run() {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        limits: ResourceLimits {
+            max_memory: Some(1000),
+            ..ResourceLimits::default()
+        },
+        ..InterpreterConfig::default()
+    });
+    interpreter
+        .run_paused(tokens("\"ab\" 300 string.repeat 1"))
+        .unwrap();
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    interpreter.step().unwrap();
+    let allocated_bytes = interpreter.context.allocated_bytes();
+    assert!(allocated_bytes >= 600);
+
+    interpreter
+        .evaluate(tokens("\"xy\" 10 string.repeat"))
+        .unwrap();
+    assert_eq!(interpreter.context.allocated_bytes(), allocated_bytes);
+
+    // The snippet alone would fit, but not together with what the run already has
+    let err = interpreter
+        .evaluate(tokens("\"ab\" 250 string.repeat"))
+        .unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::LimitExceeded(Limit::Memory)));
+    assert_eq!(interpreter.context.allocated_bytes(), allocated_bytes);
+
+    assert_eq!(interpreter.resume().unwrap(), ExecutionStatus::Completed);
+}
+
+#[test]
+fn tracer_sees_every_token_with_stacks_and_writes() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    interpreter.set_tracer(move |event| {
+        recorded.lock().unwrap().push((
+            event.token.position.column,
+            event.call_depth,
+            event.stack_before.join(" "),
+            event.stack_after.join(" "),
+            event.variable_writes.to_vec(),
+            event.error.is_some(),
+        ));
+    });
+
+    interpreter
+        .run(tokens("1 [ $x set ] call $x \"a\" +"))
+        .unwrap_err();
+    let events = events.lock().unwrap();
+    let summary: Vec<_> = events
+        .iter()
+        .map(|(column, depth, before, after, _, failed)| {
+            (*column, *depth, before.as_str(), after.as_str(), *failed)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (1, 0, "", "1", false),
+            (3, 0, "1", "1 Block(2)", false),
+            (14, 0, "1 Block(2)", "1", false),
+            (5, 1, "1", "1 $x (unset)", false),
+            (8, 1, "1 $x (unset)", "", false),
+            (19, 0, "", "$x (1)", false),
+            (22, 0, "$x (1)", "$x (1) \"a\"", false),
+            (26, 0, "$x (1) \"a\"", "$x (1)", true),
+        ]
+    );
+    let writes: Vec<_> = events.iter().flat_map(|event| event.4.clone()).collect();
+    assert_eq!(writes, vec![("x".to_string(), "1".to_string())]);
+}
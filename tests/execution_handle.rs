@@ -0,0 +1,113 @@
+use std::{thread, time::Duration};
+
+use gnarly_interpreter::{
+    interpreter::{ExecutionStatus, Interpreter, InterpreterConfig, Operand, error::ErrorKind},
+    lexer::{Lexer, SourceToken},
+};
+
+fn tokens(source: &str) -> Vec<SourceToken> {
+    Lexer::scan(source).unwrap().token_list
+}
+
+fn stack_numbers(interpreter: &Interpreter) -> Vec<f64> {
+    interpreter
+        .context
+        .current_scope_readonly()
+        .get_operand_stack()
+        .iter()
+        .map(|operand| match operand {
+            Operand::Number(value) => *value,
+            other => panic!("Expected number on stack but found: {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn cancel_from_another_thread_aborts_run() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let handle = interpreter.handle();
+
+    // Only stops when cancelled
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.cancel();
+    });
+    let err = interpreter
+        .run(tokens("[ forever ] \"forever\" def forever"))
+        .unwrap_err();
+    canceller.join().unwrap();
+    assert_eq!(err.kind, ErrorKind::Cancelled);
+    assert!(!interpreter.is_paused());
+
+    // Cancellation only applies to the run it interrupted
+    let status = interpreter.run(tokens("4 5 +")).unwrap();
+    assert_eq!(status, ExecutionStatus::Completed);
+    assert_eq!(stack_numbers(&interpreter), vec![9.0]);
+}
+
+#[test]
+fn requests_left_over_from_a_finished_run_are_dropped() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.run(tokens("1")).unwrap();
+
+    // e.g. the user hit cancel just as the run finished
+    interpreter.handle().cancel();
+    interpreter.handle().pause();
+    assert_eq!(
+        interpreter.run(tokens("2 3 +")).unwrap(),
+        ExecutionStatus::Completed
+    );
+    assert_eq!(stack_numbers(&interpreter), vec![1.0, 5.0]);
+}
+
+#[test]
+fn pause_and_resume_one_token_at_a_time() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let handle = interpreter.handle();
+
+    assert_eq!(
+        interpreter.run_paused(tokens("1 2 +")).unwrap(),
+        ExecutionStatus::Paused
+    );
+    assert!(stack_numbers(&interpreter).is_empty());
+
+    // Requesting another pause before resuming executes exactly one token
+    handle.pause();
+    assert_eq!(interpreter.resume().unwrap(), ExecutionStatus::Paused);
+    assert_eq!(stack_numbers(&interpreter), vec![1.0]);
+
+    handle.pause();
+    assert_eq!(interpreter.resume().unwrap(), ExecutionStatus::Paused);
+    assert_eq!(stack_numbers(&interpreter), vec![1.0, 2.0]);
+
+    assert_eq!(interpreter.resume().unwrap(), ExecutionStatus::Completed);
+    assert_eq!(stack_numbers(&interpreter), vec![3.0]);
+    assert!(!interpreter.is_paused());
+}
+
+#[test]
+fn run_while_paused_fails() {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.run_paused(tokens("1")).unwrap();
+
+    assert!(interpreter.run(tokens("2")).is_err());
+    assert_eq!(interpreter.resume().unwrap(), ExecutionStatus::Completed);
+    assert_eq!(stack_numbers(&interpreter), vec![1.0]);
+}
+
+#[test]
+fn interpreter_can_be_moved_to_another_thread() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Interpreter>();
+
+    // Including with a tracer set
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.set_tracer(|_| {});
+    let interpreter = thread::spawn(move || {
+        interpreter.run(tokens("1 2 +")).unwrap();
+        interpreter
+    })
+    .join()
+    .unwrap();
+    assert_eq!(stack_numbers(&interpreter), vec![3.0]);
+}
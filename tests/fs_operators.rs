@@ -0,0 +1,178 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use gnarly_interpreter::{
+    execution_context::capabilities::Capabilities,
+    interpreter::{
+        Interpreter, InterpreterConfig,
+        error::{ErrorKind, RuntimeError},
+        limits::{Limit, ResourceLimits},
+    },
+    lexer::Lexer,
+};
+
+/// The operands `source` leaves on the stack, as `print` would show them.
+fn stack_with(limits: ResourceLimits, source: &str) -> Result<Vec<String>, RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        capabilities: Capabilities::all(),
+        limits,
+        ..InterpreterConfig::default()
+    });
+    interpreter.run(Lexer::scan(source).unwrap().token_list)?;
+    let context = &interpreter.context;
+    Ok(context
+        .current_scope_readonly()
+        .get_operand_stack()
+        .iter()
+        .map(|operand| context.operand_to_string(operand).unwrap())
+        .collect())
+}
+
+fn stack(source: &str) -> Result<Vec<String>, RuntimeError> {
+    stack_with(ResourceLimits::default(), source)
+}
+
+/// A fresh, empty directory.
+fn temp_dir(name: &str) -> PathBuf {
+    let root = env::temp_dir().join(format!("gnarly_fs_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// A path as a string literal.
+fn quoted(path: &Path) -> String {
+    format!("\"{}\"", path.display())
+}
+
+#[test]
+fn write_append_and_read() {
+    let root = temp_dir("read_write");
+    let file = quoted(&root.join("notes.txt"));
+
+    stack(&format!("\"one\n\" {file} file.write 2 {file} file.append")).unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("notes.txt")).unwrap(),
+        "one\n2"
+    );
+    assert_eq!(
+        stack(&format!("{file} file.read {file} file.lines")).unwrap(),
+        ["one\n2", "{ \"one\", \"2\" }"]
+    );
+
+    // Writing replaces the whole file, and appending creates it if needed
+    let other = quoted(&root.join("other.txt"));
+    stack(&format!(
+        "\"a\" {other} file.append \"b\" {file} file.write"
+    ))
+    .unwrap();
+    assert_eq!(
+        stack(&format!("{other} file.read {file} file.read")).unwrap(),
+        ["a", "b"]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn exists_delete_and_list() {
+    let root = temp_dir("list");
+    fs::write(root.join("b.txt"), "").unwrap();
+    fs::write(root.join("a.gnarly"), "").unwrap();
+    fs::create_dir(root.join("sub")).unwrap();
+    let dir = quoted(&root);
+    let file = quoted(&root.join("b.txt"));
+
+    assert_eq!(
+        stack(&format!("{dir} dir.list")).unwrap(),
+        ["{ \"a.gnarly\", \"b.txt\", \"sub\" }"]
+    );
+    assert_eq!(
+        stack(&format!(
+            "{file} file.exists {dir} file.exists {file} file.delete {file} file.exists"
+        ))
+        .unwrap(),
+        ["true", "true", "false"]
+    );
+    assert_eq!(
+        stack(&format!("{dir} dir.list")).unwrap(),
+        ["{ \"a.gnarly\", \"sub\" }"]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn missing_files_are_errors() {
+    let root = temp_dir("missing");
+    let missing = quoted(&root.join("missing.txt"));
+    for operator in ["file.read", "file.lines", "file.delete", "dir.list"] {
+        let err = stack(&format!("{missing} {operator}")).unwrap_err();
+        assert!(
+            err.message.starts_with(&format!(
+                "{}: Cannot access '{}': ",
+                operator,
+                root.join("missing.txt").display()
+            )),
+            "{}",
+            err.message
+        );
+    }
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn file_read_counts_against_the_memory_limit() {
+    let root = temp_dir("memory");
+    fs::write(root.join("big.txt"), "x".repeat(100_000)).unwrap();
+    let limits = || ResourceLimits {
+        max_memory: Some(1000),
+        ..ResourceLimits::default()
+    };
+    for operator in ["file.read", "file.lines"] {
+        let err = stack_with(
+            limits(),
+            &format!("{} {}", quoted(&root.join("big.txt")), operator),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::LimitExceeded(Limit::Memory)),
+            "{}",
+            operator
+        );
+    }
+
+    fs::write(root.join("small.txt"), "small").unwrap();
+    assert_eq!(
+        stack_with(
+            limits(),
+            &format!("{} file.read", quoted(&root.join("small.txt")))
+        )
+        .unwrap(),
+        ["small"]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+// Separators differ on Windows
+#[cfg(unix)]
+#[test]
+fn paths() {
+    assert_eq!(
+        stack("\"dir\" \"file.txt\" path.join \"dir/\" \"file.txt\" path.join").unwrap(),
+        ["dir/file.txt", "dir/file.txt"]
+    );
+    // Joining an absolute path replaces the first
+    assert_eq!(stack("\"dir\" \"/abs\" path.join").unwrap(), ["/abs"]);
+    assert_eq!(
+        stack("\"a/b/notes.txt\" path.basename \"/\" path.basename").unwrap(),
+        ["notes.txt", "null"]
+    );
+    assert_eq!(
+        stack("\"notes.tar.gz\" path.extension \"Makefile\" path.extension").unwrap(),
+        ["gz", "null"]
+    );
+}
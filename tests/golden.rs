@@ -0,0 +1,105 @@
+//! Runs every program in `code_examples/` through the `gnarly` binary and compares its exit
+//! code, stdout and stderr with the matching `.expected` file. Some examples are sketches that
+//! do not lex or run; their errors are recorded like any other output.
+//!
+//! Run with `BLESS=1 cargo test --test golden` to regenerate the `.expected` files after an
+//! intended change in output.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+const EXAMPLES_DIR: &str = "code_examples";
+
+fn example_files() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(EXAMPLES_DIR);
+    let mut files: Vec<PathBuf> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "gnarly")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Run an example and render everything it did in the `.expected` file format.
+fn run_example(path: &Path) -> String {
+    let relative_path = Path::new(EXAMPLES_DIR).join(path.file_name().unwrap());
+    let output = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+        .arg(&relative_path)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    let exit_code = match output.status.code() {
+        Some(code) => code.to_string(),
+        None => "none (killed by signal)".to_string(),
+    };
+    format!(
+        "exit code: {}\n--- stdout ---\n{}--- stderr ---\n{}",
+        exit_code,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+/// Describe where `actual` first differs from `expected`, line by line.
+fn describe_mismatch(expected: &str, actual: &str) -> String {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let line = expected_lines
+        .iter()
+        .zip(&actual_lines)
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or(expected_lines.len().min(actual_lines.len()));
+
+    format!(
+        "first difference at line {}\n    expected: {:?}\n    actual:   {:?}",
+        line + 1,
+        expected_lines.get(line).copied().unwrap_or("<end of file>"),
+        actual_lines.get(line).copied().unwrap_or("<end of file>")
+    )
+}
+
+#[test]
+fn code_examples_match_expected_output() {
+    let bless = env::var_os("BLESS").is_some_and(|value| value != "0");
+    let mut failures = Vec::new();
+
+    for path in example_files() {
+        let actual = run_example(&path);
+        let expected_path = path.with_extension("expected");
+
+        if bless {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{}: {}",
+                path.display(),
+                describe_mismatch(&expected, &actual)
+            )),
+            Err(_) => failures.push(format!(
+                "{}: missing {}",
+                path.display(),
+                expected_path.display()
+            )),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} example(s) did not match their expected output (run with BLESS=1 to update):\n\n{}",
+        failures.len(),
+        failures.join("\n\n")
+    );
+}
@@ -0,0 +1,105 @@
+//! Operators that read stdin or write to stdout and stderr, run through the `gnarly` binary.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Run `source` with `stdin` piped in, returning its exit code, stdout and stderr.
+fn gnarly(args: &[&str], source: &str, stdin: &str) -> (Option<i32>, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+        .args(args)
+        .args(["-e", source])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Dropping stdin closes it, so the program sees the end of its input. The program may
+    // stop reading part-way through, so the rest of it can fail to be written.
+    let mut child_stdin = child.stdin.take().unwrap();
+    let _ = child_stdin.write_all(stdin.as_bytes());
+    drop(child_stdin);
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn read_line_strips_line_endings_and_ends_with_null() {
+    let (code, stdout, _) = gnarly(
+        &[],
+        "read.line print read.line print read.line print read.line print",
+        "one\r\ntwo\nthree",
+    );
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "one\ntwo\nthree\nnull\n");
+}
+
+#[test]
+fn read_number() {
+    let (code, stdout, _) = gnarly(&[], "read.number read.number + print", "2.5\n4\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "6.5\n");
+
+    let (_, stdout, _) = gnarly(&[], "read.number print", "");
+    assert_eq!(stdout, "null\n");
+
+    let (code, _, stderr) = gnarly(&[], "read.number print", "abc\n");
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("abc"), "{}", stderr);
+}
+
+#[test]
+fn read_all() {
+    let (code, stdout, _) = gnarly(
+        &[],
+        "read.line print read.all string.length print",
+        "first\n🐈 and\nthe rest",
+    );
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "first\n14\n");
+
+    let (_, stdout, _) = gnarly(&[], "read.all string.length print", "");
+    assert_eq!(stdout, "0\n");
+}
+
+#[test]
+fn read_all_counts_against_the_memory_limit() {
+    let input = "x".repeat(100_000);
+    let (code, stdout, stderr) = gnarly(
+        &["--max-memory", "1000"],
+        "read.all string.length print",
+        &input,
+    );
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "");
+    assert!(
+        stderr.contains("Exceeded memory limit: 'read.all' would allocate"),
+        "{}",
+        stderr
+    );
+
+    let (code, stdout, _) = gnarly(
+        &["--max-memory", "1000"],
+        "read.all string.length print",
+        "small",
+    );
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "5\n");
+}
+
+#[test]
+fn write_and_eprint() {
+    let (code, stdout, stderr) = gnarly(
+        &[],
+        "\"a\" write 1 write \"b\" print \"oops\" eprint \"c\" print.no_newline",
+        "",
+    );
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "a1b\nc");
+    assert_eq!(stderr, "oops\n");
+}
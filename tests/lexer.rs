@@ -0,0 +1,128 @@
+use std::{fs, path::Path};
+
+use gnarly_interpreter::lexer::{Lexer, Position, Token};
+
+fn tokens(source: &str) -> Vec<Token> {
+    Lexer::scan(source)
+        .unwrap()
+        .token_list
+        .into_iter()
+        .map(|source_token| source_token.token)
+        .collect()
+}
+
+fn round_trip(source: &str) {
+    let original = tokens(source);
+    let printed: Vec<String> = original.iter().map(|token| token.to_string()).collect();
+    assert_eq!(tokens(&printed.join(" ")), original, "{}", source);
+}
+
+#[test]
+fn display_round_trips_through_the_lexer() {
+    round_trip("1 2.5 -3 + \"a \\\"quoted\\\" \\\\ string\" $x set { [ dup ] } print");
+    round_trip("\"two\nlines\" print");
+
+    let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("code_examples");
+    for entry in fs::read_dir(examples).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "gnarly")
+        {
+            // Preprocessor directives are not tokens, so only lex what the lexer accepts
+            let source = fs::read_to_string(&path).unwrap();
+            if Lexer::scan(&source).is_ok() {
+                round_trip(&source);
+            }
+        }
+    }
+}
+
+#[test]
+fn string_escapes() {
+    let string = |source: &str| match &tokens(source)[..] {
+        [Token::StringLiteral(value)] => value.clone(),
+        tokens => panic!("Expected a single string but found {:?}", tokens),
+    };
+    assert_eq!(string(r#""say \"hi\"""#), r#"say "hi""#);
+    assert_eq!(string(r#""back\\slash""#), r"back\slash");
+    // A string can end with an escaped backslash
+    assert_eq!(string(r#""ends with \\""#), r"ends with \");
+    // Other backslashes are kept as they are, e.g. for regex patterns
+    assert_eq!(string(r#""\d+\.\n""#), r"\d+\.\n");
+}
+
+#[test]
+fn tokens_span_from_first_to_last_character() {
+    let spans: Vec<(Position, Position)> = Lexer::scan("12 \"ab\ncd\" $name {}")
+        .unwrap()
+        .token_list
+        .into_iter()
+        .map(|source_token| (source_token.position, source_token.end))
+        .collect();
+    let at = |line, column| Position { line, column };
+    assert_eq!(
+        spans,
+        vec![
+            (at(1, 1), at(1, 2)),
+            (at(1, 4), at(2, 3)),
+            (at(2, 5), at(2, 9)),
+            (at(2, 11), at(2, 11)),
+            (at(2, 12), at(2, 12)),
+        ]
+    );
+}
+
+#[test]
+fn unterminated_strings_and_brackets_are_errors() {
+    let error = |source: &str| {
+        let err = Lexer::scan(source).err().unwrap();
+        (err.message, err.position.line, err.position.column)
+    };
+    let expected = |message: &str, line, column| (message.to_string(), line, column);
+
+    assert_eq!(
+        error("1 \"abc"),
+        expected("Unterminated string, expected '\"'", 1, 3)
+    );
+    assert_eq!(
+        error("\"ends with \\\""),
+        expected("Unterminated string, expected '\"'", 1, 1)
+    );
+    // The innermost bracket that is still open
+    assert_eq!(
+        error("{ { } {\n1"),
+        expected("Unclosed '{', expected '}'", 1, 7)
+    );
+    assert_eq!(error("[ 1 2"), expected("Unclosed '[', expected ']'", 1, 1));
+    // Too many closing brackets are for the interpreter to report
+    assert!(Lexer::scan("1 } ]").is_ok());
+
+    // Unless it is asked not to check
+    let lexer = Lexer::scan_incomplete("{ \"abc").unwrap();
+    let tokens: Vec<&Token> = lexer
+        .token_list
+        .iter()
+        .map(|source_token| &source_token.token)
+        .collect();
+    assert_eq!(
+        tokens,
+        [&Token::ScopeStart, &Token::StringLiteral("abc".to_string())]
+    );
+}
+
+#[test]
+fn shebang_line_is_ignored() {
+    let lexer = Lexer::scan("#!/usr/bin/env gnarly\n1 print").unwrap();
+    assert_eq!(
+        lexer.token_list[0].position,
+        Position { line: 2, column: 1 }
+    );
+    assert_eq!(
+        tokens("#!/usr/bin/env gnarly\n1 print"),
+        vec![
+            Token::NumberLiteral(1.0),
+            Token::Operator("print".to_string())
+        ]
+    );
+}
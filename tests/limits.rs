@@ -0,0 +1,140 @@
+use std::{
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use gnarly_interpreter::{
+    execution_context::capabilities::Capabilities,
+    interpreter::{
+        Interpreter, InterpreterConfig,
+        error::{ErrorKind, RuntimeError},
+        limits::{Limit, ResourceLimits},
+    },
+    lexer::Lexer,
+};
+
+fn run(limits: ResourceLimits, source: &str) -> Result<(), RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        capabilities: Capabilities::all(),
+        limits,
+        ..InterpreterConfig::default()
+    });
+    interpreter
+        .run(Lexer::scan(source).unwrap().token_list)
+        .map(|_| ())
+}
+
+fn exceeded(limits: ResourceLimits, source: &str) -> Limit {
+    match run(limits, source).unwrap_err().kind {
+        ErrorKind::LimitExceeded(limit) => limit,
+        kind => panic!("Expected a limit to be exceeded but failed with {:?}", kind),
+    }
+}
+
+#[test]
+fn max_steps() {
+    let limits = || ResourceLimits {
+        max_steps: Some(5),
+        ..ResourceLimits::default()
+    };
+    run(limits(), "1 2 + 3 +").unwrap();
+    assert_eq!(exceeded(limits(), "1 2 + 3 + 4 +"), Limit::Steps);
+}
+
+#[test]
+fn max_stack_size() {
+    let limits = || ResourceLimits {
+        max_stack_size: Some(3),
+        ..ResourceLimits::default()
+    };
+    run(limits(), "1 2 3").unwrap();
+    // Operands in enclosing scopes count too
+    assert_eq!(exceeded(limits(), "1 2 { 3 4 }"), Limit::StackSize);
+}
+
+#[test]
+fn max_scope_depth() {
+    let limits = || ResourceLimits {
+        max_scope_depth: Some(3),
+        ..ResourceLimits::default()
+    };
+    run(limits(), "{ { } }").unwrap();
+    assert_eq!(exceeded(limits(), "{ { { } } }"), Limit::ScopeDepth);
+}
+
+#[test]
+fn max_memory() {
+    let limits = || ResourceLimits {
+        max_memory: Some(1000),
+        ..ResourceLimits::default()
+    };
+    run(limits(), "\"ab\" 100 string.repeat").unwrap();
+    assert_eq!(
+        exceeded(
+            limits(),
+            "\"ab\" 300 string.repeat $s set $s $s string.concat"
+        ),
+        Limit::Memory
+    );
+
+    // Stopped before allocating, rather than after the allocation has already happened
+    assert_eq!(
+        exceeded(limits(), "\"x\" 1000000000000 string.repeat"),
+        Limit::Memory
+    );
+    assert_eq!(
+        exceeded(limits(), "\"x\" 1000000000000 \"y\" string.pad_left"),
+        Limit::Memory
+    );
+    assert_eq!(
+        exceeded(
+            limits(),
+            "\"ab\" 300 string.repeat \"\" \"xyz\" regex.replace_all"
+        ),
+        Limit::Memory
+    );
+}
+
+#[test]
+fn max_duration() {
+    let limits = || ResourceLimits {
+        max_duration: Some(Duration::from_millis(100)),
+        ..ResourceLimits::default()
+    };
+    run(limits(), "1 2 +").unwrap();
+    assert_eq!(
+        exceeded(limits(), "[ forever ] \"forever\" def forever"),
+        Limit::Duration
+    );
+}
+
+#[test]
+fn limits_from_the_command_line() {
+    let cases = [
+        (["--max-steps", "2"], "1 2 3", "step"),
+        (["--max-stack", "2"], "1 2 3", "stack size"),
+        (["--max-depth", "1"], "{ }", "scope depth"),
+        (["--max-memory", "10"], "\"x\" 100 string.repeat", "memory"),
+        (
+            ["--max-time", "50"],
+            "[ forever ] \"forever\" def forever",
+            "time",
+        ),
+    ];
+    for (flags, source, limit) in cases {
+        let output = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+            .args(flags)
+            .args(["-e", source])
+            .stdin(Stdio::null())
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{:?}", flags);
+        assert!(
+            stderr.contains(&format!("Exceeded {} limit", limit)),
+            "{:?}: {}",
+            flags,
+            stderr
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use gnarly_interpreter::{
+    execution_context::capabilities::Capabilities,
+    interpreter::{
+        Interpreter, InterpreterConfig,
+        error::{ErrorKind, RuntimeError},
+        limits::{Limit, ResourceLimits},
+    },
+    lexer::Lexer,
+};
+
+/// A directory of modules, each given as (relative path, source).
+fn module_dir(name: &str, modules: &[(&str, &str)]) -> PathBuf {
+    let directory = env::temp_dir().join(format!("gnarly_modules_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    for (path, source) in modules {
+        let path = directory.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    directory
+}
+
+fn run_main(directory: &Path, limits: ResourceLimits, source: &str) -> Result<(), RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        capabilities: Capabilities::all(),
+        limits,
+        ..InterpreterConfig::default()
+    });
+    let tokens = Lexer::scan_file(source, &directory.join("main.gnarly"))
+        .unwrap()
+        .token_list;
+    interpreter.run(tokens).map(|_| ())
+}
+
+#[test]
+fn imports_share_the_limits_of_the_run() {
+    // 14 tokens each, and 2 for each import
+    let busy = "1 1 + 1 + 1 + 1 + 1 + [ ] \"f\" def";
+    let directory = module_dir(
+        "limits",
+        &[("a.gnarly", busy), ("b.gnarly", busy), ("c.gnarly", busy)],
+    );
+    let limits = || ResourceLimits {
+        max_steps: Some(35),
+        ..ResourceLimits::default()
+    };
+
+    run_main(
+        &directory,
+        limits(),
+        "\"a.gnarly\" import \"b.gnarly\" import",
+    )
+    .unwrap();
+    let err = run_main(
+        &directory,
+        limits(),
+        "\"a.gnarly\" import \"b.gnarly\" import \"c.gnarly\" import",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::LimitExceeded(Limit::Steps));
+
+    let allocating = "\"ab\" 200 string.repeat $s set [ ] \"f\" def";
+    let directory = module_dir(
+        "memory",
+        &[("a.gnarly", allocating), ("b.gnarly", allocating)],
+    );
+    let limits = ResourceLimits {
+        max_memory: Some(600),
+        ..ResourceLimits::default()
+    };
+    let err = run_main(
+        &directory,
+        limits,
+        "\"a.gnarly\" import \"b.gnarly\" import",
+    )
+    .unwrap_err();
+    assert_eq!(err.kind, ErrorKind::LimitExceeded(Limit::Memory));
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn modules_with_the_same_name_cannot_both_be_imported() {
+    let directory = module_dir(
+        "namespaces",
+        &[
+            ("a/utils.gnarly", "[ 1 ] \"one\" def"),
+            ("b/utils.gnarly", "[ 2 ] \"two\" def"),
+        ],
+    );
+
+    // Importing the same module again is fine
+    run_main(
+        &directory,
+        ResourceLimits::default(),
+        "\"a/utils.gnarly\" import \"a/utils.gnarly\" import utils.one",
+    )
+    .unwrap();
+
+    let err = run_main(
+        &directory,
+        ResourceLimits::default(),
+        "\"a/utils.gnarly\" import \"b/utils.gnarly\" import",
+    )
+    .unwrap_err();
+    assert!(err.message.contains("as 'utils', as '"), "{}", err.message);
+    assert!(err.message.contains("already is"), "{}", err.message);
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn module_variables_are_kept_for_its_operators() {
+    let directory = module_dir(
+        "variables",
+        &[(
+            "utils.gnarly",
+            "10 $x set\n\
+             [ $x ] \"get\" def\n\
+             [ $x 1 + $x set ] \"bump\" def\n\
+             [ \"x=$x\" ] \"show\" def\n",
+        )],
+    );
+
+    // The importing file has its own `$x`, and importing again keeps the module's. A `.` after a
+    // variable is still just text.
+    run_main(
+        &directory,
+        ResourceLimits::default(),
+        "1 $x set \"utils.gnarly\" import\n\
+         utils.get 10 assert.eq\n\
+         utils.bump utils.get 11 assert.eq\n\
+         utils.show \"x=11\" assert.eq\n\
+         $x 1 assert.eq\n\
+         \"utils.gnarly\" import utils.get 11 assert.eq\n\
+         \"$x.txt\" \"1.txt\" assert.eq",
+    )
+    .unwrap();
+
+    fs::remove_dir_all(directory).unwrap();
+}
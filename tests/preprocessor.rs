@@ -0,0 +1,283 @@
+//! `@define`, `@if`/`@else`, `@include` and the line map that points back at the original files.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use gnarly_interpreter::{
+    execution_context::capabilities::{Capabilities, FilesystemAccess},
+    lexer::Lexer,
+    preprocessor::{PreprocessedSource, Preprocessor},
+};
+
+fn preprocess(symbols: &[&str], source: &str) -> Result<PreprocessedSource, String> {
+    let symbols: Vec<String> = symbols.iter().map(|symbol| symbol.to_string()).collect();
+    Preprocessor::new(&symbols, &Capabilities::all()).process_source(Path::new("<test>"), source)
+}
+
+/// A fresh directory with the given files in it.
+fn temp_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = env::temp_dir().join(format!(
+        "gnarly_preprocessor_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    for (file, contents) in files {
+        fs::write(root.join(file), contents).unwrap();
+    }
+    root
+}
+
+#[test]
+fn define_replaces_whole_words_outside_of_strings() {
+    let preprocessed = preprocess(
+        &[],
+        "@define GREETING \"hi\"\nGREETING print \"GREETING\" print GREETINGS\n",
+    )
+    .unwrap();
+    assert_eq!(
+        preprocessed.source,
+        "\"hi\" print \"GREETING\" print GREETINGS\n"
+    );
+
+    // Also inside strings that span several lines
+    let preprocessed = preprocess(&["X=1"], "\"a\nX\" X\n").unwrap();
+    assert_eq!(preprocessed.source, "\"a\nX\" 1\n");
+
+    // Brackets end a word just like whitespace, as they do in the lexer
+    let preprocessed = preprocess(
+        &["GREETING=\"hi\""],
+        "{GREETING} [GREETING] {\"GREETING\"} $GREETING\n",
+    )
+    .unwrap();
+    assert_eq!(
+        preprocessed.source,
+        "{\"hi\"} [\"hi\"] {\"GREETING\"} $GREETING\n"
+    );
+}
+
+#[test]
+fn nested_if_and_else() {
+    let source = "\
+@if OUTER
+outer
+@if INNER
+both
+@else
+only_outer
+@endif
+@else
+@if INNER
+only_inner
+@else
+neither
+@endif
+@endif
+";
+    let cases = [
+        (&["OUTER", "INNER"][..], "outer\nboth\n"),
+        (&["OUTER"][..], "outer\nonly_outer\n"),
+        (&["INNER"][..], "only_inner\n"),
+        (&[][..], "neither\n"),
+    ];
+    for (symbols, expected) in cases {
+        assert_eq!(
+            preprocess(symbols, source).unwrap().source,
+            expected,
+            "{:?}",
+            symbols
+        );
+    }
+
+    // `@define` in a dropped section does not define anything
+    let preprocessed = preprocess(&[], "@if NOPE\n@define X\n@endif\n@if !X\nkept\n@endif\n");
+    assert_eq!(preprocessed.unwrap().source, "kept\n");
+
+    for (source, message) in [
+        ("@else\n", "@else without @if"),
+        ("@if X\n@else\n@else\n@endif\n", "Duplicate @else"),
+        ("@endif\n", "@endif without @if"),
+        ("@if X\n", "@if without matching @endif"),
+    ] {
+        let err = preprocess(&[], source).err().unwrap();
+        assert!(err.ends_with(message), "{}", err);
+    }
+}
+
+#[test]
+fn include_cycles_are_errors() {
+    let root = temp_dir(
+        "cycle",
+        &[
+            ("a.gnarly", "1\n@include \"b.gnarly\"\n"),
+            ("b.gnarly", "2\n@include \"a.gnarly\"\n"),
+            ("self.gnarly", "@include \"self.gnarly\"\n"),
+            (
+                "twice.gnarly",
+                "@include \"one.gnarly\"\n@include \"one.gnarly\"\n",
+            ),
+            ("one.gnarly", "1\n"),
+        ],
+    );
+    let process =
+        |file: &str| Preprocessor::new(&[], &Capabilities::all()).process_file(&root.join(file));
+
+    let err = process("a.gnarly").err().unwrap();
+    assert!(err.contains("Cannot include"), "{}", err);
+    assert!(err.contains("a.gnarly' inside itself"), "{}", err);
+    assert!(process("self.gnarly").is_err());
+    // Including the same file twice is not a cycle
+    assert_eq!(process("twice.gnarly").unwrap().source, "1\n1\n");
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn lines_map_back_to_the_original_files() {
+    let root = temp_dir(
+        "lines",
+        &[
+            (
+                "main.gnarly",
+                "@define X 1\nX print\n@include \"other.gnarly\"\n2 print\n",
+            ),
+            ("other.gnarly", "@if NOPE\nskipped\n@endif\n3 print\n"),
+        ],
+    );
+    let main = root.join("main.gnarly");
+    let other = root.join("other.gnarly");
+    let preprocessed = Preprocessor::new(&[], &Capabilities::all())
+        .process_file(&main)
+        .unwrap();
+    assert_eq!(preprocessed.source, "1 print\n3 print\n2 print\n");
+
+    let original_line = |line| {
+        preprocessed
+            .original_line(line)
+            .map(|(file, line)| (file.to_path_buf(), line))
+    };
+    assert_eq!(original_line(1), Some((main.clone(), 2)));
+    assert_eq!(original_line(2), Some((other.clone(), 4)));
+    assert_eq!(original_line(3), Some((main.clone(), 4)));
+    assert_eq!(original_line(0), None);
+    assert_eq!(original_line(4), None);
+
+    let mut tokens = Lexer::scan(&preprocessed.source).unwrap().token_list;
+    preprocessed.map_tokens(&mut tokens);
+    let positions: Vec<(PathBuf, usize, usize)> = tokens
+        .iter()
+        .map(|token| {
+            (
+                token.file.as_ref().unwrap().to_path_buf(),
+                token.position.line,
+                token.position.column,
+            )
+        })
+        .collect();
+    assert_eq!(
+        positions,
+        [
+            (main.clone(), 2, 1),
+            (main.clone(), 2, 3),
+            (other.clone(), 4, 1),
+            (other, 4, 3),
+            (main.clone(), 4, 1),
+            (main, 4, 3),
+        ]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn include_requires_filesystem_access() {
+    let root = temp_dir("access", &[("inside.gnarly", "1\n")]);
+    fs::create_dir_all(root.join("allowed")).unwrap();
+    fs::write(
+        root.join("allowed/main.gnarly"),
+        "@include \"../inside.gnarly\"\n",
+    )
+    .unwrap();
+    let main = root.join("allowed/main.gnarly");
+
+    let err = Preprocessor::new(&[], &Capabilities::none())
+        .process_file(&main)
+        .err()
+        .unwrap();
+    assert!(
+        err.contains("Permission denied: '@include' is not allowed to access"),
+        "{}",
+        err
+    );
+
+    // Scoped access is checked against the included file, not the one including it
+    let capabilities = |path: PathBuf| Capabilities {
+        filesystem: FilesystemAccess::Paths(vec![path]),
+        ..Capabilities::none()
+    };
+    assert!(
+        Preprocessor::new(&[], &capabilities(root.join("allowed")))
+            .process_file(&main)
+            .is_err()
+    );
+    Preprocessor::new(&[], &capabilities(root.clone()))
+        .process_file(&main)
+        .unwrap();
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+fn gnarly(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn symbols_from_the_command_line() {
+    let source = "@if DEBUG\n\"debug\" print\n@else\n\"release\" print\n@endif\nGREETING print";
+
+    let (code, stdout, _) = gnarly(&["-D", "DEBUG", "-D", "GREETING=\"hi\"", "-e", source]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "debug\nhi\n");
+
+    let (code, stdout, _) = gnarly(&["--define", "GREETING=2", "-e", source]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "release\n2\n");
+}
+
+#[test]
+fn include_is_denied_from_the_command_line() {
+    let root = temp_dir(
+        "cli",
+        &[
+            ("main.gnarly", "@include \"other.gnarly\"\n"),
+            ("other.gnarly", "\"included\" print\n"),
+        ],
+    );
+    let main = root.join("main.gnarly").display().to_string();
+
+    let (code, stdout, _) = gnarly(&[&main]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "included\n");
+
+    // The program itself is read, as it was named on the command line, but not what it includes
+    let (code, stdout, stderr) = gnarly(&["--deny-fs", &main]);
+    assert_eq!(code, Some(3));
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("Preprocessor error: "), "{}", stderr);
+    assert!(stderr.contains("Permission denied"), "{}", stderr);
+
+    fs::remove_dir_all(root).unwrap();
+}
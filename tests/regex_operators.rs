@@ -0,0 +1,98 @@
+use gnarly_interpreter::{
+    execution_context::REGEX_CACHE_SIZE,
+    interpreter::{Interpreter, InterpreterConfig, error::RuntimeError},
+    lexer::Lexer,
+};
+
+fn run(source: &str) -> Result<Interpreter, RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.run(Lexer::scan(source).unwrap().token_list)?;
+    Ok(interpreter)
+}
+
+/// The operands `source` leaves on the stack, as `print` would show them.
+fn stack(source: &str) -> Vec<String> {
+    let interpreter = run(source).unwrap();
+    let context = &interpreter.context;
+    context
+        .current_scope_readonly()
+        .get_operand_stack()
+        .iter()
+        .map(|operand| context.operand_to_string(operand).unwrap())
+        .collect()
+}
+
+#[test]
+fn match_and_find() {
+    assert_eq!(
+        stack("\"a1b22\" \"\\d+\" regex.match \"abc\" \"\\d\" regex.match"),
+        ["true", "false"]
+    );
+    assert_eq!(
+        stack("\"a1b22\" \"\\d+\" regex.find \"abc\" \"\\d\" regex.find"),
+        ["1", "null"]
+    );
+    assert_eq!(
+        stack("\"a1b22\" \"\\d+\" regex.find_all \"abc\" \"\\d\" regex.find_all"),
+        ["{ \"1\", \"22\" }", "{  }"]
+    );
+}
+
+#[test]
+fn captures() {
+    assert_eq!(
+        stack("\"on 2024-06\" \"(?<year>\\d+)-(?<month>\\d+)\" regex.captures"),
+        ["{ \"2024-06\", \"2024\", \"06\", month = \"06\", year = \"2024\" }"]
+    );
+    // Groups that did not take part in the match are null
+    assert_eq!(
+        stack("\"ab\" \"a(x)?(b)\" regex.captures"),
+        ["{ \"ab\", null, \"b\" }"]
+    );
+    assert_eq!(stack("\"abc\" \"\\d\" regex.captures"), ["null"]);
+}
+
+#[test]
+fn replace_and_split() {
+    assert_eq!(
+        stack("\"a1b2\" \"\\d\" \"#\" regex.replace \"a1b2\" \"\\d\" \"#\" regex.replace_all"),
+        ["a#b2", "a#b#"]
+    );
+    assert_eq!(
+        stack("\"2024-06\" \"(?<y>\\d+)-(\\d+)\" \"${2}/${y}\" regex.replace"),
+        ["06/2024"]
+    );
+    assert_eq!(
+        stack("\"a1b22c\" \"\\d+\" regex.split"),
+        ["{ \"a\", \"b\", \"c\" }"]
+    );
+}
+
+#[test]
+fn invalid_patterns_are_errors() {
+    for operator in ["regex.match", "regex.find", "regex.captures", "regex.split"] {
+        let err = run(&format!("\"abc\" \"(\" {}", operator)).err().unwrap();
+        assert!(
+            err.message.starts_with("Invalid regular expression '(': "),
+            "{}",
+            err.message
+        );
+    }
+    // Errors can be caught like any other
+    let caught = stack("[ \"abc\" \"[\" \"x\" regex.replace_all ] [ ] try");
+    assert!(
+        caught[0].contains("message = \"Invalid regular expression '['"),
+        "{:?}",
+        caught
+    );
+}
+
+#[test]
+fn regex_cache_is_bounded() {
+    // A different pattern every time, as if they were built at runtime
+    let source: Vec<String> = (0..REGEX_CACHE_SIZE * 2)
+        .map(|i| format!("\"a\" \"a{}\" regex.match", i))
+        .collect();
+    let interpreter = run(&source.join(" ")).unwrap();
+    assert_eq!(interpreter.context.regex_cache_len(), REGEX_CACHE_SIZE);
+}
@@ -0,0 +1,217 @@
+//! Capabilities, both through the library and the command line `--allow-*`/`--deny-*` flags.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use gnarly_interpreter::{
+    execution_context::{
+        ExecutionContext,
+        capabilities::{Capabilities, Capability, FilesystemAccess},
+    },
+    interpreter::{
+        Interpreter, InterpreterConfig,
+        error::{ErrorKind, RuntimeError},
+    },
+    lexer::Lexer,
+};
+
+fn run(capabilities: Capabilities, source: &str) -> Result<(), RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig {
+        capabilities,
+        ..InterpreterConfig::default()
+    });
+    interpreter
+        .run(Lexer::scan(source).unwrap().token_list)
+        .map(|_| ())
+}
+
+/// A directory with `allowed/inside.txt` and `secret.txt` next to `allowed`.
+fn sandbox_dir(name: &str) -> PathBuf {
+    let root = env::temp_dir().join(format!("gnarly_sandbox_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("allowed")).unwrap();
+    fs::write(root.join("allowed/inside.txt"), "inside").unwrap();
+    fs::write(root.join("secret.txt"), "secret").unwrap();
+    root
+}
+
+fn quoted(path: &Path) -> String {
+    format!("\"{}\"", path.display())
+}
+
+#[test]
+fn default_capabilities_deny_everything() {
+    let capabilities = Capabilities::default();
+    assert!(!capabilities.process);
+    assert!(!capabilities.stdout);
+    assert!(!capabilities.allows_path("anything.txt"));
+}
+
+#[test]
+fn each_denied_capability_has_its_own_permission_error() {
+    for (source, capability) in [("\"hi\" print", "stdout"), ("read.line", "stdin")] {
+        let err = run(Capabilities::none(), source).unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::PermissionDenied),
+            "{}",
+            source
+        );
+        assert!(
+            err.message
+                .ends_with(&format!("requires the '{}' capability", capability)),
+            "{}: {}",
+            source,
+            err.message
+        );
+    }
+
+    // No builtin operator needs the other capabilities yet, but they are checked the same way
+    let context = ExecutionContext::new(Capabilities::none());
+    for (capability, name) in [
+        (Capability::Env, "env"),
+        (Capability::Process, "process"),
+        (Capability::Time, "time"),
+        (Capability::Random, "random"),
+    ] {
+        let err = context.require_capability("op", capability).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::PermissionDenied));
+        assert_eq!(
+            err.message,
+            format!("'op' requires the '{}' capability", name)
+        );
+    }
+
+    // Only the denied capability is affected
+    let capabilities = Capabilities {
+        stdin: false,
+        ..Capabilities::all()
+    };
+    assert!(capabilities.allows(Capability::Random));
+    assert!(!capabilities.allows(Capability::Stdin));
+    run(capabilities.clone(), "\"hi\" string.length").unwrap();
+    assert!(run(capabilities, "read.line").is_err());
+}
+
+#[test]
+fn filesystem_access_is_scoped_to_allowed_paths() {
+    let root = sandbox_dir("scope");
+    let allowed = root.join("allowed");
+    let capabilities = Capabilities {
+        filesystem: FilesystemAccess::Paths(vec![allowed.clone()]),
+        ..Capabilities::none()
+    };
+
+    assert!(capabilities.allows_path(&allowed.join("inside.txt").display().to_string()));
+    // Files that do not exist yet, e.g. for `file.write`
+    assert!(capabilities.allows_path(&allowed.join("new.txt").display().to_string()));
+    assert!(!capabilities.allows_path(&root.join("secret.txt").display().to_string()));
+    assert!(!capabilities.allows_path(&allowed.join("../secret.txt").display().to_string()));
+    assert!(!capabilities.allows_path(&allowed.join("../new.txt").display().to_string()));
+
+    let read = |path: &Path| run(capabilities.clone(), &format!("{} file.read", quoted(path)));
+    read(&allowed.join("inside.txt")).unwrap();
+    let err = read(&allowed.join("../secret.txt")).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::PermissionDenied));
+    assert!(
+        err.message
+            .starts_with("'file.read' is not allowed to access")
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_cannot_escape_allowed_paths() {
+    let root = sandbox_dir("symlink");
+    let allowed = root.join("allowed");
+    std::os::unix::fs::symlink(&root, allowed.join("escape")).unwrap();
+    std::os::unix::fs::symlink(root.join("secret.txt"), allowed.join("secret_link.txt")).unwrap();
+    let capabilities = Capabilities {
+        filesystem: FilesystemAccess::Paths(vec![allowed.clone()]),
+        ..Capabilities::none()
+    };
+
+    for path in [
+        allowed.join("escape/secret.txt"),
+        allowed.join("secret_link.txt"),
+        allowed.join("escape/new.txt"),
+    ] {
+        assert!(
+            !capabilities.allows_path(&path.display().to_string()),
+            "{}",
+            path.display()
+        );
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+fn gnarly(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn deny_all_with_specific_allow_flags() {
+    let (code, stdout, _) = gnarly(&["--deny-all", "--allow-stdout", "-e", "\"hi\" print"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "hi\n");
+
+    let (code, _, stderr) = gnarly(&["--deny-all", "--allow-stdout", "-e", "read.line print"]);
+    assert_eq!(code, Some(1));
+    assert!(
+        stderr.contains("'read.line' requires the 'stdin' capability"),
+        "{}",
+        stderr
+    );
+
+    // Nothing is allowed unless asked for, not even printing
+    let (code, stdout, stderr) = gnarly(&["--deny-all", "--allow-time", "-e", "\"hi\" print"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("'stdout' capability"), "{}", stderr);
+
+    // A --deny-* flag wins over the matching --allow-*
+    let (code, _, _) = gnarly(&["--allow-stdin", "--deny-stdin", "-e", "read.line"]);
+    assert_eq!(code, Some(1));
+}
+
+#[test]
+fn deny_all_with_allow_fs_only_reaches_allowed_paths() {
+    let root = sandbox_dir("cli");
+    let allowed = root.join("allowed");
+    let allow_fs = allowed.display().to_string();
+    let read = |path: PathBuf| {
+        gnarly(&[
+            "--deny-all",
+            "--allow-stdout",
+            "--allow-fs",
+            &allow_fs,
+            "-e",
+            &format!("{} file.read print", quoted(&path)),
+        ])
+    };
+
+    let (code, stdout, _) = read(allowed.join("inside.txt"));
+    assert_eq!(code, Some(0));
+    assert_eq!(stdout, "inside\n");
+
+    let (code, stdout, stderr) = read(root.join("secret.txt"));
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("Permission denied"), "{}", stderr);
+
+    fs::remove_dir_all(root).unwrap();
+}
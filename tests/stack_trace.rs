@@ -0,0 +1,41 @@
+use gnarly_interpreter::{
+    interpreter::{Interpreter, InterpreterConfig},
+    lexer::{Lexer, Position},
+};
+
+fn trace_of(source: &str) -> Vec<(String, Position)> {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    let err = interpreter
+        .run(Lexer::scan(source).unwrap().token_list)
+        .unwrap_err();
+    err.trace
+        .into_iter()
+        .map(|frame| (frame.name, frame.position))
+        .collect()
+}
+
+fn at(line: usize, column: usize) -> Position {
+    Position { line, column }
+}
+
+#[test]
+fn error_in_user_defined_operator_has_full_trace() {
+    let trace = trace_of("[ 1 \"x\" + ] \"bad\" def\n[ bad ] \"outer\" def\n[ outer ] call");
+
+    assert_eq!(
+        trace,
+        vec![
+            ("bad".to_string(), at(1, 9)),
+            ("outer".to_string(), at(2, 3)),
+            ("call".to_string(), at(3, 3)),
+            ("<main>".to_string(), at(3, 11)),
+        ]
+    );
+}
+
+#[test]
+fn caught_errors_unwind_the_call_stack() {
+    let trace = trace_of("[ [ \"oops\" throw ] call ] [ ] try\n\"again\" throw");
+
+    assert_eq!(trace, vec![("<main>".to_string(), at(2, 9))]);
+}
@@ -0,0 +1,118 @@
+use gnarly_interpreter::{
+    interpreter::{Interpreter, InterpreterConfig, error::RuntimeError},
+    lexer::Lexer,
+};
+
+/// The operands `source` leaves on the stack, as `print` would show them.
+fn stack(source: &str) -> Result<Vec<String>, RuntimeError> {
+    let mut interpreter = Interpreter::new(InterpreterConfig::default());
+    interpreter.run(Lexer::scan(source).unwrap().token_list)?;
+    let context = &interpreter.context;
+    Ok(context
+        .current_scope_readonly()
+        .get_operand_stack()
+        .iter()
+        .map(|operand| context.operand_to_string(operand).unwrap())
+        .collect())
+}
+
+fn result(source: &str) -> String {
+    let stack = stack(source).unwrap();
+    assert_eq!(stack.len(), 1, "{}: {:?}", source, stack);
+    stack[0].clone()
+}
+
+fn error(source: &str) -> String {
+    stack(source).unwrap_err().message
+}
+
+#[test]
+fn case_trim_and_length() {
+    assert_eq!(result("\"ab\" \"cd\" string.concat"), "abcd");
+    assert_eq!(result("\"Gnarly 🐈\" string.upper"), "GNARLY 🐈");
+    assert_eq!(result("\"Gnarly\" string.lower"), "gnarly");
+    assert_eq!(result("\"  gnarly \n\" string.trim"), "gnarly");
+    // Characters, not bytes
+    assert_eq!(result("\"🐈🐈\" string.length"), "2");
+    assert_eq!(result("\"\" string.length"), "0");
+    assert_eq!(result("\"🐈 tac\" string.reverse"), "cat 🐈");
+}
+
+#[test]
+fn split_join_and_chars() {
+    assert_eq!(
+        result("\"a,b,,c\" \",\" string.split"),
+        "{ \"a\", \"b\", \"\", \"c\" }"
+    );
+    assert_eq!(result("\"a🐈\" \"\" string.split"), "{ \"a\", \"🐈\" }");
+    assert_eq!(result("\"a🐈\" string.chars"), "{ \"a\", \"🐈\" }");
+    assert_eq!(result("{ \"a\" 1 \"b\" } \"-\" string.join"), "a-1-b");
+    assert_eq!(result("{ } \"-\" string.join"), "");
+}
+
+#[test]
+fn substring() {
+    assert_eq!(result("\"🐈 cat\" 2 5 string.substring"), "cat");
+    assert_eq!(result("\"cat\" 1 1 string.substring"), "");
+    assert_eq!(
+        error("\"cat\" 2 4 string.substring"),
+        "string.substring: Range 2..4 is out of bounds for string of length 3"
+    );
+    assert_eq!(
+        error("\"cat\" 2 1 string.substring"),
+        "string.substring: Range 2..1 is out of bounds for string of length 3"
+    );
+    assert_eq!(
+        error("\"cat\" 0.5 1 string.substring"),
+        "string.substring: Expected a non-negative integer but found: 0.5"
+    );
+}
+
+#[test]
+fn replace_and_search() {
+    assert_eq!(result("\"a-b-c\" \"-\" \"+\" string.replace"), "a+b+c");
+    assert_eq!(
+        error("\"abc\" \"\" \"x\" string.replace"),
+        "string.replace: Pattern cannot be empty"
+    );
+    assert_eq!(
+        stack(
+            "\"gnarly\" \"gn\" string.starts_with \"gnarly\" \"ly\" string.ends_with \
+             \"gnarly\" \"ar\" string.contains \"gnarly\" \"ly\" string.starts_with"
+        )
+        .unwrap(),
+        ["true", "true", "true", "false"]
+    );
+}
+
+#[test]
+fn repeat_and_pad() {
+    assert_eq!(result("\"ab\" 3 string.repeat"), "ababab");
+    assert_eq!(result("\"ab\" 0 string.repeat"), "");
+    assert_eq!(result("\"7\" 3 \"0\" string.pad_left"), "007");
+    assert_eq!(result("\"7\" 3 \"🐈\" string.pad_right"), "7🐈🐈");
+    // Already wide enough
+    assert_eq!(result("\"gnarly\" 3 \" \" string.pad_left"), "gnarly");
+    assert_eq!(
+        error("\"7\" 3 \"ab\" string.pad_left"),
+        "string.pad_left: Padding must be a single character but found: \"ab\""
+    );
+    assert_eq!(
+        error("\"ab\" -1 string.repeat"),
+        "string.repeat: Expected a non-negative integer but found: -1"
+    );
+}
+
+#[test]
+fn results_too_large_to_allocate_are_errors() {
+    // No memory limit is set, so nothing else would stop these
+    for source in [
+        "\"a\" 100000000000 string.repeat",
+        "\"ab\" 10000000000000000000 string.repeat",
+        "\"a\" 100000000000 \"x\" string.pad_left",
+        "\"a\" 100000000000 \"x\" string.pad_right",
+    ] {
+        let message = error(source);
+        assert!(message.ends_with(": Result is too large"), "{}", message);
+    }
+}
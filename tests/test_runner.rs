@@ -0,0 +1,148 @@
+//! Runs `gnarly test` over a directory of test files.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+fn test_dir(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!(
+        "gnarly_test_runner_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn gnarly_test(args: &[&str], directory: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gnarly-interpreter"))
+        .arg("test")
+        .args(args)
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn runs_test_files_and_writes_junit() {
+    let directory = test_dir("results");
+    fs::write(
+        directory.join("math_test.gnarly"),
+        "[ 2 * ] \"double\" def\n\
+         test \"double\" { 2 double 4 assert.eq }\n\
+         test \"double again\" { 3 double 6 assert.eq }\n",
+    )
+    .unwrap();
+    fs::create_dir(directory.join("nested")).unwrap();
+    fs::write(
+        directory.join("nested/broken_test.gnarly"),
+        "1 2 assert.eq\n",
+    )
+    .unwrap();
+    // Neither of these are test files
+    fs::write(directory.join("helpers.gnarly"), "\"oops\" throw\n").unwrap();
+    fs::create_dir(directory.join(".hidden")).unwrap();
+    fs::write(
+        directory.join(".hidden/hidden_test.gnarly"),
+        "\"oops\" throw\n",
+    )
+    .unwrap();
+
+    let output = gnarly_test(&[".", "--junit", "results.xml"], &directory);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("test double ... ok"), "{}", stdout);
+    assert!(
+        stdout.contains("test broken_test.gnarly ... FAILED"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("test result: FAILED. 2 passed; 1 failed"),
+        "{}",
+        stdout
+    );
+
+    let junit = fs::read_to_string(directory.join("results.xml")).unwrap();
+    assert!(
+        junit.contains(r#"<testsuites name="gnarly" tests="3" failures="1""#),
+        "{}",
+        junit
+    );
+    assert!(
+        junit.contains(r#"<testcase name="double again""#),
+        "{}",
+        junit
+    );
+    assert!(
+        junit.contains(r#"<testcase name="broken_test.gnarly""#),
+        "{}",
+        junit
+    );
+    assert!(
+        junit.contains("<failure message=\"Assertion failed: "),
+        "{}",
+        junit
+    );
+    assert!(!junit.contains("hidden_test"), "{}", junit);
+
+    // Only the passing file
+    let output = gnarly_test(&["math_test.gnarly"], &directory);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("test result: ok. 2 passed; 0 failed")
+    );
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn no_test_files_is_a_failure() {
+    let directory = test_dir("empty");
+    fs::write(directory.join("helpers.gnarly"), "1 assert\n").unwrap();
+
+    let output = gnarly_test(&[], &directory);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("No *_test.gnarly files found in '.'")
+    );
+
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_files_are_preprocessed() {
+    let directory = test_dir("preprocessed");
+    fs::write(directory.join("helpers.gnarly"), "[ 2 * ] \"double\" def\n").unwrap();
+    fs::write(
+        directory.join("helpers_test.gnarly"),
+        "@include \"helpers.gnarly\"\n\
+         @define FOUR 4\n\
+         test \"double\" { 2 double FOUR assert.eq }\n\
+         @if SLOW\n\
+         test \"slow\" { 1 2 assert.eq }\n\
+         @endif\n",
+    )
+    .unwrap();
+
+    let output = gnarly_test(&[], &directory);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0), "{}", stdout);
+    assert!(
+        stdout.contains("test result: ok. 1 passed; 0 failed"),
+        "{}",
+        stdout
+    );
+
+    // Symbols from the command line reach the test files too
+    let output = gnarly_test(&["-D", "SLOW"], &directory);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("test slow ... FAILED"), "{}", stdout);
+
+    fs::remove_dir_all(directory).unwrap();
+}